@@ -1,33 +1,175 @@
 // crates/core/src/ai_client.rs
 
-use anyhow::Result;
+use std::path::Path;
+
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::foundry_client::FoundryClient;
+use crate::openai_client::OpenAiClient;
+use crate::retry::{RetryPolicy, Retrying};
+use crate::scripted_client::{RecordingClient, ScriptedClient};
+use crate::streaming::{emit_response, OnDelta};
+
 /// Abstract AI/LLM client for chat completions with tool support.
 ///
 /// Implementations can use Azure Foundry, OpenAI, Ollama, etc.
+/// See `chat_client_from_env` for the providers that ship with the runtime.
 pub trait AiClient {
     /// Send a chat completion request with optional tools.
     fn chat(&self, request: ChatRequest) -> Result<ChatResponse>;
+
+    /// Like `chat`, but report text and tool-call arguments to `on_delta`
+    /// as they are generated.
+    ///
+    /// Clients that can't stream send the whole response as deltas at once.
+    fn chat_stream(&self, request: ChatRequest, on_delta: OnDelta<'_>) -> Result<ChatResponse> {
+        let response = self.chat(request)?;
+        emit_response(&response, on_delta);
+        Ok(response)
+    }
+}
+
+impl<C: AiClient + ?Sized> AiClient for Box<C> {
+    fn chat(&self, request: ChatRequest) -> Result<ChatResponse> {
+        (**self).chat(request)
+    }
+
+    fn chat_stream(&self, request: ChatRequest, on_delta: OnDelta<'_>) -> Result<ChatResponse> {
+        (**self).chat_stream(request, on_delta)
+    }
+}
+
+/// Which part of the runtime a chat client is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatRole {
+    /// The task agent (and reranker).
+    Agent,
+    /// The mutation agent, which can use a different (coding-focused) model.
+    Mutation,
+}
+
+/// Construct the chat client for a role from environment variables.
+///
+/// The provider is CHAT_PROVIDER for the agent and MUTATION_CHAT_PROVIDER
+/// (falling back to CHAT_PROVIDER) for the mutation agent:
+///
+/// - "foundry" (default): FoundryClient, needs the FOUNDRY_* variables; the
+///   mutation agent uses FOUNDRY_MUTATION_DEPLOYMENT if set
+/// - "openai": OpenAiClient, any OpenAI-compatible `/v1/chat/completions`
+///   server; the mutation agent reads OPENAI_MUTATION_* variables first
+/// - "scripted": ScriptedClient replaying the cassette at CHAT_CASSETTE;
+///   the mutation agent reads MUTATION_CHAT_CASSETTE first
+///
+/// Network clients retry transient failures per `RetryPolicy::from_env`
+/// and stream completions unless CHAT_STREAM is "0" or "false".
+/// If CHAT_RECORD (MUTATION_CHAT_RECORD) names a file, every interaction is
+/// appended to it as a cassette.
+pub fn chat_client_from_env(role: ChatRole) -> Result<Box<dyn AiClient>> {
+    chat_client_from_vars(role, |name| std::env::var(name).ok())
+}
+
+/// `chat_client_from_env` with variables looked up through `vars` instead
+/// of the process environment.
+pub fn chat_client_from_vars(
+    role: ChatRole,
+    vars: impl Fn(&str) -> Option<String>,
+) -> Result<Box<dyn AiClient>> {
+    let var = |name: &str| match role {
+        ChatRole::Agent => vars(name),
+        ChatRole::Mutation => vars(&format!("MUTATION_{}", name)),
+    };
+
+    let provider = var("CHAT_PROVIDER")
+        .or_else(|| vars("CHAT_PROVIDER"))
+        .unwrap_or_else(|| "foundry".to_string());
+
+    let retry = RetryPolicy::from_vars(&vars)?;
+    let stream = vars("CHAT_STREAM")
+        .map(|v| !matches!(v.to_lowercase().as_str(), "0" | "false" | "no"))
+        .unwrap_or(true);
+    let client: Box<dyn AiClient> = match (provider.trim().to_lowercase().as_str(), role) {
+        ("foundry" | "azure", ChatRole::Agent) => Box::new(Retrying::new(
+            FoundryClient::from_vars_with_deployment_var("FOUNDRY_CHAT_DEPLOYMENT", &vars)?
+                .with_streaming(stream),
+            retry,
+        )),
+        ("foundry" | "azure", ChatRole::Mutation) => Box::new(Retrying::new(
+            FoundryClient::from_vars_with_deployment_var("FOUNDRY_MUTATION_DEPLOYMENT", &vars)
+                .or_else(|_| {
+                    FoundryClient::from_vars_with_deployment_var("FOUNDRY_CHAT_DEPLOYMENT", &vars)
+                })?
+                .with_streaming(stream),
+            retry,
+        )),
+        ("openai", ChatRole::Agent) => Box::new(Retrying::new(
+            OpenAiClient::from_vars_with_prefix("OPENAI", &vars)?.with_streaming(stream),
+            retry,
+        )),
+        ("openai", ChatRole::Mutation) => Box::new(Retrying::new(
+            OpenAiClient::from_vars_with_prefix("OPENAI_MUTATION", &vars)?.with_streaming(stream),
+            retry,
+        )),
+        ("scripted", _) => {
+            let path = var("CHAT_CASSETTE")
+                .or_else(|| vars("CHAT_CASSETTE"))
+                .context(match role {
+                    ChatRole::Agent => "CHAT_CASSETTE not set",
+                    ChatRole::Mutation => "neither MUTATION_CHAT_CASSETTE nor CHAT_CASSETTE is set",
+                })?;
+            Box::new(ScriptedClient::from_cassette(Path::new(&path))?)
+        }
+        (other, _) => anyhow::bail!(
+            "unknown chat provider '{}' (expected 'foundry', 'openai' or 'scripted')",
+            other
+        ),
+    };
+
+    Ok(match var("CHAT_RECORD") {
+        Some(path) => Box::new(RecordingClient::new(client, path)),
+        None => client,
+    })
 }
 
 /// A chat completion request.
+///
+/// Generation settings are optional and omitted from the body when unset,
+/// so each server applies its own defaults.
 #[derive(Debug, Clone, Serialize)]
 pub struct ChatRequest {
-    pub messages: Vec<Value>,
+    pub messages: Vec<Message>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Only sent alongside tools; servers reject it otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
 }
 
 impl ChatRequest {
-    pub fn new(messages: Vec<Value>) -> Self {
+    pub fn new(messages: Vec<Message>) -> Self {
         Self {
             messages,
             tools: Vec::new(),
             tool_choice: None,
+            temperature: None,
+            max_tokens: None,
+            seed: None,
+            stop: Vec::new(),
+            parallel_tool_calls: None,
+            response_format: None,
         }
     }
 
@@ -36,39 +178,322 @@ impl ChatRequest {
         self.tool_choice = Some("auto".to_string());
         self
     }
+
+    /// Apply a caller's generation settings. Call after `with_tools`.
+    pub fn with_params(mut self, params: &GenerationParams) -> Self {
+        self.temperature = params.temperature;
+        self.max_tokens = params.max_tokens;
+        self.seed = params.seed;
+        if !self.tools.is_empty() {
+            self.parallel_tool_calls = params.parallel_tool_calls;
+        }
+        self
+    }
+
+    pub fn with_stop(mut self, stop: &[&str]) -> Self {
+        self.stop = stop.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = Some(format);
+        self
+    }
+}
+
+/// Constrains the shape of the model's reply.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    /// Any valid JSON object; the prompt must ask for JSON.
+    JsonObject,
+    /// JSON matching a schema; needs a server that supports structured outputs.
+    JsonSchema {
+        json_schema: JsonSchemaFormat,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonSchemaFormat {
+    pub name: String,
+    pub schema: Value,
+    pub strict: bool,
+}
+
+/// Sampling settings a caller applies to its requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationParams {
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub seed: Option<u64>,
+    pub parallel_tool_calls: Option<bool>,
+}
+
+impl GenerationParams {
+    /// Override `defaults` from environment variables named `<prefix>_...`.
+    ///
+    /// - `<prefix>_TEMPERATURE`
+    /// - `<prefix>_MAX_TOKENS`
+    /// - `<prefix>_SEED`
+    /// - `<prefix>_PARALLEL_TOOL_CALLS` ("true"/"false")
+    ///
+    /// "none" unsets a setting, for models that reject it.
+    pub fn from_env(prefix: &str, defaults: Self) -> Result<Self> {
+        fn read<T: std::str::FromStr>(
+            prefix: &str,
+            name: &str,
+            default: Option<T>,
+        ) -> Result<Option<T>> {
+            let var = format!("{}_{}", prefix, name);
+            match std::env::var(&var) {
+                Ok(v) if v.eq_ignore_ascii_case("none") => Ok(None),
+                Ok(v) => v
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| anyhow::anyhow!("invalid {} '{}'", var, v)),
+                Err(_) => Ok(default),
+            }
+        }
+
+        Ok(Self {
+            temperature: read(prefix, "TEMPERATURE", defaults.temperature)?,
+            max_tokens: read(prefix, "MAX_TOKENS", defaults.max_tokens)?,
+            seed: read(prefix, "SEED", defaults.seed)?,
+            parallel_tool_calls: read(prefix, "PARALLEL_TOOL_CALLS", defaults.parallel_tool_calls)?,
+        })
+    }
+}
+
+/// A message in a conversation, serialized in the chat completions wire
+/// format (`{"role": "...", ...}`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum Message {
+    System {
+        content: String,
+    },
+    User {
+        content: String,
+    },
+    Assistant {
+        #[serde(default)]
+        content: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tool_calls: Vec<ToolCall>,
+    },
+    Tool(ToolResult),
+}
+
+/// The output of a tool call, sent back to the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolResult {
+    pub tool_call_id: String,
+    pub name: String,
+    pub content: String,
+}
+
+impl Message {
+    pub fn system(content: impl Into<String>) -> Self {
+        Message::System {
+            content: content.into(),
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Message::User {
+            content: content.into(),
+        }
+    }
+
+    /// An assistant message with text only.
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Message::Assistant {
+            content: Some(content.into()),
+            tool_calls: Vec::new(),
+        }
+    }
+
+    /// The result of `call`.
+    pub fn tool_result(call: &ToolCall, content: impl Into<String>) -> Self {
+        Message::Tool(ToolResult {
+            tool_call_id: call.id.clone(),
+            name: call.function.name.clone(),
+            content: content.into(),
+        })
+    }
+
+    /// Text content, if any.
+    pub fn content(&self) -> Option<&str> {
+        match self {
+            Message::System { content } | Message::User { content } => Some(content),
+            Message::Assistant { content, .. } => content.as_deref(),
+            Message::Tool(result) => Some(&result.content),
+        }
+    }
+}
+
+impl From<ChatMessage> for Message {
+    fn from(message: ChatMessage) -> Self {
+        Message::Assistant {
+            content: message.content,
+            tool_calls: message.tool_calls.unwrap_or_default(),
+        }
+    }
 }
 
 /// A chat completion response.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatResponse {
     pub choices: Vec<ChatChoice>,
+    /// Tokens billed for the call, when the server reports them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+/// Token counts for one or more chat calls.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatChoice {
     pub message: ChatMessage,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChatMessage {
     pub role: String,
     #[serde(default)]
     pub content: Option<String>,
     #[serde(default)]
-    pub tool_calls: Option<Vec<ChatToolCall>>,
+    pub tool_calls: Option<Vec<ToolCall>>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct ChatToolCall {
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolCall {
     pub id: String,
     #[serde(rename = "type")]
     pub call_type: String,
-    pub function: ChatToolFunction,
+    pub function: ToolFunction,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct ChatToolFunction {
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ToolFunction {
     pub name: String,
     /// Raw JSON string of the arguments.
     pub arguments: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_chat_client_from_env_selects_provider_per_role() {
+        let dir = std::env::temp_dir().join(format!("se_chat_roles_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cassette = |name: &str| {
+            let path = dir.join(format!("{}.jsonl", name));
+            let line = json!({
+                "request": {},
+                "response": {"choices": [{"message": {"role": "assistant", "content": name}}]}
+            });
+            std::fs::write(&path, format!("{}\n", line)).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let reply = |vars: &HashMap<&str, String>, role: ChatRole| -> Result<String> {
+            let client = chat_client_from_vars(role, |name| vars.get(name).cloned())?;
+            let response = client.chat(ChatRequest::new(vec![]))?;
+            Ok(response.choices[0]
+                .message
+                .content
+                .clone()
+                .unwrap_or_default())
+        };
+
+        let mut vars = HashMap::from([("CHAT_PROVIDER", "scripted".to_string())]);
+        let err = reply(&vars, ChatRole::Mutation).unwrap_err().to_string();
+        assert!(err.contains("MUTATION_CHAT_CASSETTE"), "{}", err);
+
+        // The mutation agent falls back to the agent's cassette...
+        vars.insert("CHAT_CASSETTE", cassette("agent"));
+        assert_eq!(reply(&vars, ChatRole::Agent).unwrap(), "agent");
+        assert_eq!(reply(&vars, ChatRole::Mutation).unwrap(), "agent");
+
+        // ...unless it has its own.
+        vars.insert("MUTATION_CHAT_CASSETTE", cassette("mutation"));
+        assert_eq!(reply(&vars, ChatRole::Agent).unwrap(), "agent");
+        assert_eq!(reply(&vars, ChatRole::Mutation).unwrap(), "mutation");
+
+        // MUTATION_CHAT_PROVIDER only affects the mutation agent.
+        vars.insert("MUTATION_CHAT_PROVIDER", "carrier-pigeon".to_string());
+        assert_eq!(reply(&vars, ChatRole::Agent).unwrap(), "agent");
+        let err = reply(&vars, ChatRole::Mutation).unwrap_err().to_string();
+        assert!(err.contains("unknown chat provider 'carrier-pigeon'"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_generation_settings_are_serialized_only_when_set() {
+        let bare = serde_json::to_value(ChatRequest::new(vec![])).unwrap();
+        assert_eq!(bare, json!({"messages": []}));
+
+        let params = GenerationParams {
+            temperature: Some(0.0),
+            max_tokens: Some(16),
+            seed: Some(7),
+            parallel_tool_calls: Some(false),
+        };
+        let plain = serde_json::to_value(
+            ChatRequest::new(vec![])
+                .with_params(&params)
+                .with_stop(&["\n"])
+                .with_response_format(ResponseFormat::JsonObject),
+        )
+        .unwrap();
+        assert_eq!(plain["temperature"], 0.0);
+        assert_eq!(plain["max_tokens"], 16);
+        assert_eq!(plain["seed"], 7);
+        assert_eq!(plain["stop"], json!(["\n"]));
+        assert_eq!(plain["response_format"], json!({"type": "json_object"}));
+        assert!(plain.get("parallel_tool_calls").is_none());
+
+        let with_tools = serde_json::to_value(
+            ChatRequest::new(vec![])
+                .with_tools(vec![json!({"type": "function"})])
+                .with_params(&params)
+                .with_response_format(ResponseFormat::JsonSchema {
+                    json_schema: JsonSchemaFormat {
+                        name: "answer".to_string(),
+                        schema: json!({"type": "object"}),
+                        strict: true,
+                    },
+                }),
+        )
+        .unwrap();
+        assert_eq!(with_tools["parallel_tool_calls"], false);
+        assert_eq!(with_tools["response_format"]["type"], "json_schema");
+        assert_eq!(
+            with_tools["response_format"]["json_schema"]["name"],
+            "answer"
+        );
+    }
+}
@@ -4,61 +4,346 @@ use std::collections::HashMap;
 use anyhow::{Context, Result};
 
 use crate::embedding::Embedder;
+use crate::index_document::IndexDocumentOptions;
+use crate::registry_backend::{CachedEmbedding, RegistryBackend};
 use crate::types::CapabilityRecord;
 
-/// In-memory index from capability id -> embedding.
+/// Default minimum similarity for a capability to count as a match.
+const DEFAULT_MIN_SCORE: f32 = 0.25;
+/// Default maximum number of capabilities surfaced per task.
+const DEFAULT_MAX_K: usize = 3;
+/// Default score drop that ends the selection early.
+const DEFAULT_SCORE_GAP: f32 = 0.15;
+/// Default MMR trade-off between relevance (1.0) and diversity (0.0).
+const DEFAULT_MMR_LAMBDA: f32 = 0.7;
+/// Default weight of the run-history prior blended into similarity.
+const DEFAULT_USAGE_WEIGHT: f32 = 0.15;
+
+/// How to turn a similarity ranking into the capabilities shown to the agent.
 ///
-/// Uses linear scan + cosine similarity. That's fine for an MVP.
+/// Instead of a fixed k, a candidate is kept while it clears `min_score`,
+/// fewer than `max_k` have been kept, and its score hasn't dropped more than
+/// `score_gap` below the previous kept candidate. With `mmr_lambda` set, the
+/// `max_k` slots are instead filled by maximal marginal relevance from every
+/// candidate clearing `min_score`, so near-duplicates don't crowd out
+/// alternatives; the gap cutoff would drop exactly those alternatives, so it
+/// doesn't apply.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetrievalOptions {
+    /// Candidates scoring below this are not a match.
+    pub min_score: f32,
+    /// Upper bound on the number of matches.
+    pub max_k: usize,
+    /// Stop at the first drop larger than this between consecutive scores.
+    /// Not used with MMR.
+    pub score_gap: Option<f32>,
+    /// MMR relevance/diversity trade-off; `None` keeps pure similarity order.
+    pub mmr_lambda: Option<f32>,
+    /// Weight of a reliability/popularity prior blended into similarity
+    /// (`(1 - w) * similarity + w * prior`) to order matches; thresholds
+    /// still apply to similarity. 0 disables it.
+    pub usage_weight: f32,
+}
+
+impl Default for RetrievalOptions {
+    fn default() -> Self {
+        Self {
+            min_score: DEFAULT_MIN_SCORE,
+            max_k: DEFAULT_MAX_K,
+            score_gap: Some(DEFAULT_SCORE_GAP),
+            mmr_lambda: Some(DEFAULT_MMR_LAMBDA),
+            usage_weight: DEFAULT_USAGE_WEIGHT,
+        }
+    }
+}
+
+impl RetrievalOptions {
+    /// Read overrides from the environment, falling back to the defaults.
+    ///
+    /// - RETRIEVAL_MIN_SCORE (default: 0.25)
+    /// - RETRIEVAL_MAX_K (default: 3)
+    /// - RETRIEVAL_SCORE_GAP (default: 0.15, "none" disables the cutoff)
+    /// - RETRIEVAL_MMR_LAMBDA (default: 0.7, "none" disables reranking)
+    /// - RETRIEVAL_USAGE_WEIGHT (default: 0.15, 0 disables the run-history prior)
+    pub fn from_env() -> Result<Self> {
+        let mut opts = Self::default();
+
+        if let Ok(v) = std::env::var("RETRIEVAL_MIN_SCORE") {
+            opts.min_score = v
+                .parse()
+                .with_context(|| format!("RETRIEVAL_MIN_SCORE is not a number: {}", v))?;
+        }
+        if let Ok(v) = std::env::var("RETRIEVAL_MAX_K") {
+            opts.max_k = v
+                .parse()
+                .with_context(|| format!("RETRIEVAL_MAX_K is not a number: {}", v))?;
+        }
+        if let Ok(v) = std::env::var("RETRIEVAL_SCORE_GAP") {
+            opts.score_gap = if v.eq_ignore_ascii_case("none") {
+                None
+            } else {
+                Some(
+                    v.parse()
+                        .with_context(|| format!("RETRIEVAL_SCORE_GAP is not a number: {}", v))?,
+                )
+            };
+        }
+
+        if let Ok(v) = std::env::var("RETRIEVAL_MMR_LAMBDA") {
+            opts.mmr_lambda = if v.eq_ignore_ascii_case("none") {
+                None
+            } else {
+                Some(
+                    v.parse()
+                        .with_context(|| format!("RETRIEVAL_MMR_LAMBDA is not a number: {}", v))?,
+                )
+            };
+        }
+
+        if let Ok(v) = std::env::var("RETRIEVAL_USAGE_WEIGHT") {
+            opts.usage_weight = v
+                .parse()
+                .with_context(|| format!("RETRIEVAL_USAGE_WEIGHT is not a number: {}", v))?;
+        }
+
+        if opts.max_k == 0 {
+            anyhow::bail!("RETRIEVAL_MAX_K must be at least 1");
+        }
+        if !(0.0..=1.0).contains(&opts.usage_weight) {
+            anyhow::bail!("RETRIEVAL_USAGE_WEIGHT must be between 0 and 1");
+        }
+        if let Some(lambda) = opts.mmr_lambda {
+            if !(0.0..=1.0).contains(&lambda) {
+                anyhow::bail!("RETRIEVAL_MMR_LAMBDA must be between 0 and 1");
+            }
+        }
+
+        Ok(opts)
+    }
+
+    /// Candidates from a ranking (sorted best first) that clear the threshold
+    /// and gap cutoff. Not capped at `max_k`.
+    pub fn candidates(&self, ranked: &[(String, f32)]) -> Vec<(String, f32)> {
+        let mut candidates: Vec<(String, f32)> = Vec::new();
+
+        for (id, score) in ranked {
+            if *score < self.min_score {
+                break;
+            }
+            if let (Some(gap), Some((_, prev))) = (self.score_gap, candidates.last()) {
+                if prev - score > gap {
+                    break;
+                }
+            }
+            candidates.push((id.clone(), *score));
+        }
+
+        candidates
+    }
+
+    /// Candidates from a ranking (sorted best first) that clear the
+    /// threshold, ignoring the gap cutoff. Used as the MMR pool.
+    pub fn above_threshold(&self, ranked: &[(String, f32)]) -> Vec<(String, f32)> {
+        ranked
+            .iter()
+            .take_while(|(_, score)| *score >= self.min_score)
+            .cloned()
+            .collect()
+    }
+
+    /// Apply the options to a ranking sorted best first, in similarity order.
+    ///
+    /// Use `CapabilityIndex::select` to also apply MMR reranking.
+    pub fn select(&self, ranked: Vec<(String, f32)>) -> Retrieval {
+        let mut matches = self.candidates(&ranked);
+        matches.truncate(self.max_k);
+        Retrieval::new(matches, ranked, self.max_k)
+    }
+}
+
+/// Result of selecting capabilities for a task.
+#[derive(Debug, Clone, Default)]
+pub struct Retrieval {
+    /// Capabilities that cleared the relevance threshold, best first.
+    pub matches: Vec<(String, f32)>,
+    /// When nothing matched, the best candidates anyway (e.g. as mutation parents).
+    pub closest: Vec<(String, f32)>,
+}
+
+impl Retrieval {
+    /// Build from the chosen matches, falling back to the top of `ranked`
+    /// as `closest` when there are none.
+    fn new(matches: Vec<(String, f32)>, ranked: Vec<(String, f32)>, max_k: usize) -> Self {
+        let closest = if matches.is_empty() {
+            ranked.into_iter().take(max_k).collect()
+        } else {
+            Vec::new()
+        };
+        Self { matches, closest }
+    }
+
+    /// True when no capability cleared the threshold.
+    pub fn is_no_match(&self) -> bool {
+        self.matches.is_empty()
+    }
+}
+
+/// In-memory index from capability id -> embeddings.
+///
+/// Each capability has its main document vector first, followed by any
+/// extra vectors (see `IndexDocumentOptions::multi_vector`); it scores as
+/// its best-matching vector. Uses linear scan + cosine similarity. That's
+/// fine for an MVP.
 #[derive(Debug)]
 pub struct CapabilityIndex {
     dim: usize,
-    embeddings: HashMap<String, Vec<f32>>,
+    embeddings: HashMap<String, Vec<Vec<f32>>>,
 }
 
 impl CapabilityIndex {
-    /// Build an index from a set of capabilities, embedding any missing ones.
+    /// Build an index from a set of capabilities with the default documents.
     pub fn build<E: Embedder>(capabilities: &mut [CapabilityRecord], embedder: &E) -> Result<Self> {
+        Self::build_with(capabilities, embedder, &IndexDocumentOptions::default())
+    }
+
+    /// Build an index, reusing and filling the main-document vectors cached
+    /// by `registry`.
+    ///
+    /// A cached vector is only used if the same model embedded the same
+    /// document; any `embedding` the record itself carries is replaced.
+    /// Embedders without a `model_id` aren't cached.
+    pub fn build_cached<E: Embedder>(
+        capabilities: &mut [CapabilityRecord],
+        embedder: &E,
+        document: &IndexDocumentOptions,
+        registry: &dyn RegistryBackend,
+    ) -> Result<Self> {
+        let model = embedder.model_id();
+
+        let mut computed = Vec::new();
+        for (i, cap) in capabilities.iter_mut().enumerate() {
+            let main = document
+                .documents(cap)
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| cap.summary.clone());
+            cap.embedding = match &model {
+                Some(model) => registry.cached_embedding(&cap.id, model, &main)?,
+                None => None,
+            };
+            if cap.embedding.is_none() {
+                computed.push((i, main));
+            }
+        }
+
+        let index = Self::build_with(capabilities, embedder, document)?;
+
+        if let Some(model) = model {
+            let fresh: Vec<CachedEmbedding> = computed
+                .into_iter()
+                .filter_map(|(i, main)| {
+                    let cap = &capabilities[i];
+                    Some(CachedEmbedding {
+                        capability_id: cap.id.clone(),
+                        model: model.clone(),
+                        document: main,
+                        vector: cap.embedding.clone()?,
+                    })
+                })
+                .collect();
+            registry
+                .cache_embeddings(&fresh)
+                .context("failed to cache capability embeddings")?;
+        }
+
+        Ok(index)
+    }
+
+    /// Build an index, embedding each capability's documents as configured.
+    ///
+    /// A record's own `embedding` is reused as the main document vector,
+    /// whatever `document` says; `build_cached` checks it against the
+    /// document instead. Missing main vectors and all extra vectors are
+    /// requested in a single `embed_batch` call.
+    pub fn build_with<E: Embedder>(
+        capabilities: &mut [CapabilityRecord],
+        embedder: &E,
+        document: &IndexDocumentOptions,
+    ) -> Result<Self> {
         let mut embeddings = HashMap::new();
         let mut dim: Option<usize> = None;
         let total = capabilities.len();
 
-        for (i, cap) in capabilities.iter_mut().enumerate() {
+        // (capability index, is main document, text) for everything to embed.
+        let mut pending: Vec<(usize, bool, String)> = Vec::new();
+        for (i, cap) in capabilities.iter().enumerate() {
+            let mut docs = document.documents(cap).into_iter();
+            let main = docs.next().unwrap_or_else(|| cap.summary.clone());
             if cap.embedding.is_none() {
-                eprintln!(
-                    "[index] ({}/{}) Embedding capability: {} ...",
-                    i + 1,
-                    total,
-                    cap.id
-                );
-                let emb = embedder.embed(&cap.summary).with_context(|| {
-                    format!("failed to embed summary for capability {}", cap.id)
-                })?;
-                cap.embedding = Some(emb);
-                eprintln!("[index] ({}/{}) Done: {}", i + 1, total, cap.id);
-            } else {
-                eprintln!(
-                    "[index] ({}/{}) Using cached embedding: {}",
-                    i + 1,
-                    total,
-                    cap.id
+                pending.push((i, true, main));
+            }
+            pending.extend(docs.map(|doc| (i, false, doc)));
+        }
+
+        let missing = pending.iter().filter(|(_, is_main, _)| *is_main).count();
+        let mut extra: Vec<Vec<Vec<f32>>> = vec![Vec::new(); total];
+
+        if !pending.is_empty() {
+            eprintln!(
+                "[index] Embedding {} documents for {} capabilities ...",
+                pending.len(),
+                total
+            );
+            let texts: Vec<&str> = pending.iter().map(|(_, _, t)| t.as_str()).collect();
+            let embedded = embedder
+                .embed_batch(&texts)
+                .context("failed to embed capability documents")?;
+
+            if embedded.len() != pending.len() {
+                anyhow::bail!(
+                    "embedder returned {} embeddings for {} documents",
+                    embedded.len(),
+                    pending.len()
                 );
             }
 
-            let emb = cap.embedding.as_ref().unwrap();
-            if let Some(d) = dim {
-                if d != emb.len() {
-                    anyhow::bail!(
-                        "inconsistent embedding dimensions: {} vs {} for capability {}",
-                        d,
-                        emb.len(),
-                        cap.id
-                    );
+            for ((i, is_main, _), emb) in pending.iter().zip(embedded) {
+                if *is_main {
+                    capabilities[*i].embedding = Some(emb);
+                } else {
+                    extra[*i].push(emb);
+                }
+            }
+            eprintln!("[index] Done embedding {} documents", pending.len());
+        }
+
+        if missing < total {
+            eprintln!(
+                "[index] Using cached embeddings for {} capabilities",
+                total - missing
+            );
+        }
+
+        for (cap, extra) in capabilities.iter().zip(extra) {
+            let mut vectors = vec![cap.embedding.clone().unwrap()];
+            vectors.extend(extra);
+
+            for emb in &vectors {
+                if let Some(d) = dim {
+                    if d != emb.len() {
+                        anyhow::bail!(
+                            "inconsistent embedding dimensions: {} vs {} for capability {}",
+                            d,
+                            emb.len(),
+                            cap.id
+                        );
+                    }
+                } else {
+                    dim = Some(emb.len());
                 }
-            } else {
-                dim = Some(emb.len());
             }
 
-            embeddings.insert(cap.id.clone(), emb.clone());
+            embeddings.insert(cap.id.clone(), vectors);
         }
 
         Ok(Self {
@@ -75,13 +360,12 @@ impl CapabilityIndex {
         self.embeddings.is_empty()
     }
 
-    /// Embed a task description and return top-k capability ids with scores.
-    pub fn nearest_for_task<E: Embedder>(
+    /// Embed a task description, checking it matches the index dimension.
+    pub fn embed_query<E: Embedder>(
         &self,
         task_description: &str,
         embedder: &E,
-        k: usize,
-    ) -> Result<Vec<(String, f32)>> {
+    ) -> Result<Vec<f32>> {
         let query_emb = embedder
             .embed(task_description)
             .context("failed to embed task description")?;
@@ -94,16 +378,115 @@ impl CapabilityIndex {
             );
         }
 
+        Ok(query_emb)
+    }
+
+    /// Embed a task description and return top-k capability ids with scores.
+    pub fn nearest_for_task<E: Embedder>(
+        &self,
+        task_description: &str,
+        embedder: &E,
+        k: usize,
+    ) -> Result<Vec<(String, f32)>> {
+        let query_emb = self.embed_query(task_description, embedder)?;
         Ok(self.nearest_from_embedding(&query_emb, k))
     }
 
+    /// Apply retrieval options to a ranking, reranking the candidates that
+    /// clear `min_score` with MMR when `opts.mmr_lambda` is set.
+    pub fn select(&self, ranked: Vec<(String, f32)>, opts: &RetrievalOptions) -> Retrieval {
+        self.select_by(ranked, opts, |_, similarity| similarity)
+    }
+
+    /// Like `select`, but while the threshold and gap apply to the
+    /// similarities in `ranked`, the matches are ordered (and scored for MMR)
+    /// by `order(id, similarity)`, e.g. similarity blended with a prior.
+    pub fn select_by(
+        &self,
+        ranked: Vec<(String, f32)>,
+        opts: &RetrievalOptions,
+        order: impl Fn(&str, f32) -> f32,
+    ) -> Retrieval {
+        let rescore = |candidates: Vec<(String, f32)>| {
+            let mut rescored: Vec<(String, f32)> = candidates
+                .into_iter()
+                .map(|(id, similarity)| {
+                    let score = order(&id, similarity);
+                    (id, score)
+                })
+                .collect();
+            rescored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            rescored
+        };
+
+        let matches = match opts.mmr_lambda {
+            None => {
+                let mut matches = rescore(opts.candidates(&ranked));
+                matches.truncate(opts.max_k);
+                matches
+            }
+            Some(lambda) => {
+                let candidates = rescore(opts.above_threshold(&ranked));
+                self.rerank_mmr(&candidates, lambda, opts.max_k)
+            }
+        };
+        Retrieval::new(matches, rescore(ranked), opts.max_k)
+    }
+
+    /// Maximal marginal relevance over a candidate set.
+    ///
+    /// Greedily picks up to k candidates, each time taking the one maximising
+    /// `lambda * score(c) - (1 - lambda) * max sim(c, already picked)`, where
+    /// `score` is the candidate's relevance score as given.
+    /// `lambda = 1.0` is plain score order; lower values favour variety.
+    pub fn rerank_mmr(
+        &self,
+        candidates: &[(String, f32)],
+        lambda: f32,
+        k: usize,
+    ) -> Vec<(String, f32)> {
+        let mut remaining: Vec<(String, f32)> = candidates.to_vec();
+        let mut picked: Vec<(String, f32)> = Vec::new();
+
+        while picked.len() < k && !remaining.is_empty() {
+            let mut best_idx = 0;
+            let mut best_score = f32::NEG_INFINITY;
+
+            for (i, (id, relevance)) in remaining.iter().enumerate() {
+                let redundancy = picked
+                    .iter()
+                    .filter_map(|(p, _)| {
+                        Some(cosine_similarity(
+                            self.embeddings.get(id)?.first()?,
+                            self.embeddings.get(p)?.first()?,
+                        ))
+                    })
+                    .fold(0.0f32, f32::max);
+                let score = lambda * relevance - (1.0 - lambda) * redundancy;
+                if score > best_score {
+                    best_score = score;
+                    best_idx = i;
+                }
+            }
+
+            picked.push(remaining.remove(best_idx));
+        }
+
+        picked
+    }
+
     /// Given a precomputed query embedding, return top-k (capability_id, score).
+    ///
+    /// A capability's score is the best similarity over its vectors.
     pub fn nearest_from_embedding(&self, query_emb: &[f32], k: usize) -> Vec<(String, f32)> {
         let mut scored: Vec<(String, f32)> = self
             .embeddings
             .iter()
-            .map(|(id, emb)| {
-                let score = cosine_similarity(query_emb, emb);
+            .map(|(id, vectors)| {
+                let score = vectors
+                    .iter()
+                    .map(|emb| cosine_similarity(query_emb, emb))
+                    .fold(f32::NEG_INFINITY, f32::max);
                 (id.clone(), score)
             })
             .collect();
@@ -114,7 +497,8 @@ impl CapabilityIndex {
     }
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+/// Cosine similarity of two vectors (0 if either is all zeros).
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     let mut dot = 0.0f32;
     let mut na = 0.0f32;
     let mut nb = 0.0f32;
@@ -131,3 +515,268 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
 
     dot / (na.sqrt() * nb.sqrt())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::local_embedder::LocalEmbedder;
+
+    /// Wraps the local embedder and counts how it is called.
+    struct CountingEmbedder {
+        inner: LocalEmbedder,
+        single_calls: Cell<usize>,
+        batch_calls: Cell<usize>,
+    }
+
+    impl Embedder for CountingEmbedder {
+        fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            self.single_calls.set(self.single_calls.get() + 1);
+            self.inner.embed(text)
+        }
+
+        fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+            self.batch_calls.set(self.batch_calls.get() + 1);
+            texts.iter().map(|t| self.inner.embed(t)).collect()
+        }
+
+        fn model_id(&self) -> Option<String> {
+            self.inner.model_id()
+        }
+    }
+
+    #[test]
+    fn test_build_embeds_missing_in_one_batch() {
+        let embedder = CountingEmbedder {
+            inner: LocalEmbedder::new(64),
+            single_calls: Cell::new(0),
+            batch_calls: Cell::new(0),
+        };
+
+        let mut cached = CapabilityRecord::new("get_car_details", "Returns company car details.");
+        cached.embedding = Some(embedder.inner.embed(&cached.summary).unwrap());
+
+        let mut caps = vec![
+            CapabilityRecord::new("get_salary_details", "Returns employee salary details."),
+            cached,
+            CapabilityRecord::new("get_leave_balance", "Returns remaining leave balance."),
+        ];
+
+        let index = CapabilityIndex::build(&mut caps, &embedder).unwrap();
+
+        assert_eq!(index.len(), 3);
+        assert_eq!(embedder.batch_calls.get(), 1);
+        assert_eq!(embedder.single_calls.get(), 0);
+        assert!(caps.iter().all(|c| c.embedding.is_some()));
+
+        let nearest = index
+            .nearest_for_task("employee salary", &embedder, 1)
+            .unwrap();
+        assert_eq!(nearest[0].0, "get_salary_details");
+    }
+
+    fn ranked(scores: &[(&str, f32)]) -> Vec<(String, f32)> {
+        scores.iter().map(|(id, s)| (id.to_string(), *s)).collect()
+    }
+
+    #[test]
+    fn test_build_cached_reuses_vectors_for_the_same_document() {
+        let registry = crate::sqlite_registry::SqliteRegistry::open_in_memory().unwrap();
+        let embedder = CountingEmbedder {
+            inner: LocalEmbedder::new(64),
+            single_calls: Cell::new(0),
+            batch_calls: Cell::new(0),
+        };
+        let document = IndexDocumentOptions::default();
+        let mut caps = vec![
+            CapabilityRecord::new("get_salary_details", "Returns employee salary details."),
+            CapabilityRecord::new("get_car_details", "Returns company car details."),
+        ];
+
+        CapabilityIndex::build_cached(&mut caps, &embedder, &document, &registry).unwrap();
+        assert_eq!(embedder.batch_calls.get(), 1);
+        let salary = caps[0].embedding.clone();
+
+        // Same documents: nothing is embedded, and a stale vector on the
+        // record is replaced by the cached one.
+        caps[0].embedding = Some(vec![1.0; 64]);
+        CapabilityIndex::build_cached(&mut caps, &embedder, &document, &registry).unwrap();
+        assert_eq!(embedder.batch_calls.get(), 1);
+        assert_eq!(caps[0].embedding, salary);
+
+        caps[1].summary = "Returns company car and parking details.".to_string();
+        CapabilityIndex::build_cached(&mut caps, &embedder, &document, &registry).unwrap();
+        assert_eq!(embedder.batch_calls.get(), 2);
+        assert_eq!(
+            caps[1].embedding,
+            Some(embedder.inner.embed(&caps[1].summary).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_select_applies_threshold_gap_and_max_k() {
+        let opts = RetrievalOptions {
+            min_score: 0.3,
+            max_k: 3,
+            score_gap: Some(0.1),
+            mmr_lambda: None,
+            usage_weight: 0.0,
+        };
+
+        // Gap cutoff: 0.8 -> 0.75 is fine, 0.75 -> 0.5 is too big a drop.
+        let r = opts.select(ranked(&[("a", 0.8), ("b", 0.75), ("c", 0.5), ("d", 0.45)]));
+        assert_eq!(r.matches, ranked(&[("a", 0.8), ("b", 0.75)]));
+        assert!(r.closest.is_empty());
+
+        // Threshold cutoff.
+        let r = opts.select(ranked(&[("a", 0.4), ("b", 0.35), ("c", 0.29)]));
+        assert_eq!(r.matches, ranked(&[("a", 0.4), ("b", 0.35)]));
+
+        // max_k cutoff.
+        let r = opts.select(ranked(&[("a", 0.5), ("b", 0.5), ("c", 0.5), ("d", 0.5)]));
+        assert_eq!(r.matches.len(), 3);
+    }
+
+    #[test]
+    fn test_select_reports_no_match_with_closest() {
+        let opts = RetrievalOptions {
+            min_score: 0.5,
+            max_k: 2,
+            score_gap: None,
+            mmr_lambda: None,
+            usage_weight: 0.0,
+        };
+
+        let r = opts.select(ranked(&[("a", 0.2), ("b", 0.1), ("c", 0.05)]));
+        assert!(r.is_no_match());
+        assert_eq!(r.closest, ranked(&[("a", 0.2), ("b", 0.1)]));
+    }
+
+    #[test]
+    fn test_select_by_thresholds_similarity_and_orders_by_score() {
+        let mut caps = vec![
+            CapabilityRecord::new("a", "a"),
+            CapabilityRecord::new("b", "b"),
+            CapabilityRecord::new("c", "c"),
+        ];
+        let index = CapabilityIndex::build(&mut caps, &LocalEmbedder::new(16)).unwrap();
+        let opts = RetrievalOptions {
+            min_score: 0.25,
+            max_k: 3,
+            score_gap: Some(0.15),
+            mmr_lambda: None,
+            usage_weight: 0.5,
+        };
+
+        // "a" has a bad history and "c" a great one, but only similarity
+        // decides what qualifies.
+        let prior = |id: &str, _similarity: f32| match id {
+            "a" => 0.1,
+            "b" => 0.5,
+            _ => 0.9,
+        };
+        let r = index.select_by(
+            ranked(&[("a", 0.30), ("b", 0.28), ("c", 0.2)]),
+            &opts,
+            prior,
+        );
+        assert_eq!(r.matches, ranked(&[("b", 0.5), ("a", 0.1)]));
+
+        let r = index.select_by(ranked(&[("c", 0.2)]), &opts, prior);
+        assert!(r.is_no_match());
+        assert_eq!(r.closest, ranked(&[("c", 0.9)]));
+    }
+
+    #[test]
+    fn test_mmr_prefers_diverse_candidates() {
+        let mut caps = vec![
+            CapabilityRecord::new("get_salary_details", "salary"),
+            CapabilityRecord::new("get_salary_info", "salary copy"),
+            CapabilityRecord::new("get_benefits_info", "benefits"),
+        ];
+        caps[0].embedding = Some(vec![1.0, 0.0, 0.0]);
+        caps[1].embedding = Some(vec![0.99, 0.14, 0.0]);
+        caps[2].embedding = Some(vec![0.7, 0.0, 0.71]);
+
+        let embedder = LocalEmbedder::new(3);
+        let index = CapabilityIndex::build(&mut caps, &embedder).unwrap();
+        let query = [1.0, 0.0, 0.2];
+        let ranked = index.nearest_from_embedding(&query, 3);
+        assert_eq!(ranked[1].0, "get_salary_info");
+
+        let opts = RetrievalOptions {
+            min_score: 0.0,
+            max_k: 2,
+            score_gap: None,
+            mmr_lambda: Some(0.5),
+            usage_weight: 0.0,
+        };
+        let r = index.select(ranked.clone(), &opts);
+        let ids: Vec<&str> = r.matches.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["get_salary_details", "get_benefits_info"]);
+
+        // lambda = 1.0 is plain similarity order.
+        let opts = RetrievalOptions {
+            mmr_lambda: Some(1.0),
+            ..opts
+        };
+        let r = index.select(ranked, &opts);
+        assert_eq!(r.matches[1].0, "get_salary_info");
+    }
+
+    #[test]
+    fn test_mmr_sees_alternatives_below_the_gap() {
+        // Three near-duplicates at 0.80/0.79/0.78 and a distinct
+        // alternative at 0.60, more than the default gap below them.
+        let mut caps = vec![
+            CapabilityRecord::new("get_salary_details", "salary"),
+            CapabilityRecord::new("get_salary_info", "salary copy"),
+            CapabilityRecord::new("get_salary_data", "salary copy"),
+            CapabilityRecord::new("get_benefits_info", "benefits"),
+        ];
+        caps[0].embedding = Some(vec![0.80, 0.6, 0.0]);
+        caps[1].embedding = Some(vec![0.79, 0.6131, 0.0]);
+        caps[2].embedding = Some(vec![0.78, 0.6258, 0.0]);
+        caps[3].embedding = Some(vec![0.60, 0.0, 0.8]);
+
+        let embedder = LocalEmbedder::new(3);
+        let index = CapabilityIndex::build(&mut caps, &embedder).unwrap();
+        let ranked = index.nearest_from_embedding(&[1.0, 0.0, 0.0], 4);
+        assert!((ranked[3].1 - 0.60).abs() < 1e-3);
+
+        let opts = RetrievalOptions {
+            max_k: 2,
+            ..RetrievalOptions::default()
+        };
+        assert_eq!(opts.candidates(&ranked).len(), 3);
+
+        let r = index.select(ranked, &opts);
+        let ids: Vec<&str> = r.matches.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["get_salary_details", "get_benefits_info"]);
+    }
+
+    #[test]
+    fn test_multi_vector_scores_best_matching_example() {
+        let embedder = LocalEmbedder::new(256);
+        let mut caps = vec![
+            CapabilityRecord::new("get_salary_details", "Returns employee compensation."),
+            CapabilityRecord::new("get_car_details", "Returns company car details."),
+        ];
+        caps[0].example_tasks = vec!["How much does EMP001 earn per year?".to_string()];
+
+        let single = CapabilityIndex::build(&mut caps.clone(), &embedder).unwrap();
+        let opts = IndexDocumentOptions {
+            multi_vector: true,
+            ..Default::default()
+        };
+        let multi = CapabilityIndex::build_with(&mut caps, &embedder, &opts).unwrap();
+
+        let query = "How much does EMP002 earn per year?";
+        let single_top = single.nearest_for_task(query, &embedder, 1).unwrap();
+        let multi_top = multi.nearest_for_task(query, &embedder, 1).unwrap();
+
+        assert_eq!(multi_top[0].0, "get_salary_details");
+        assert!(multi_top[0].1 > single_top[0].1);
+    }
+}
@@ -1,12 +1,21 @@
 // crates/core/src/capability_registry.rs
-use std::fs;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
+use crate::registry_backend::{transition_refused, RegistryBackend, RegistryChange, RegistryEvent};
+use crate::registry_health::{HealthIssue, IssueKind};
+use crate::run_stats::{self, CapabilityRunStats, RUN_STATS_FILE};
 use crate::types::{CapabilityRecord, CapabilityStatus};
 
+/// File name of a capability's change log inside its crate directory.
+pub const HISTORY_FILE: &str = "history.jsonl";
+
 /// On-disk representation of a capability's metadata.
 ///
 /// This maps 1:1 to meta.json for now.
@@ -14,7 +23,7 @@ use crate::types::{CapabilityRecord, CapabilityStatus};
 pub struct CapabilityMeta {
     pub id: String,
     pub summary: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub embedding: Option<Vec<f32>>, // allow preload if you want later
     #[serde(default)]
     pub binary: Option<String>, // relative path to binary within the capability dir
@@ -22,6 +31,16 @@ pub struct CapabilityMeta {
     pub status: CapabilityStatus,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub replaced_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub example_tasks: Vec<String>,
 }
 
 /// Registry is responsible for loading capabilities from disk.
@@ -44,8 +63,28 @@ impl CapabilityRegistry {
     ///   crates/
     ///     <capability_id>/
     ///       meta.json
+    ///
+    /// Fails on the first unreadable meta.json; use `load_checked` to get
+    /// every problem as a `HealthIssue` instead.
     pub fn load_capabilities(&self) -> Result<Vec<CapabilityRecord>> {
+        let (records, issues) = self.scan()?;
+
+        if let Some(issue) = issues.iter().find(|i| {
+            matches!(
+                i.kind,
+                IssueKind::MalformedMeta(_) | IssueKind::UnknownStatus(_)
+            )
+        }) {
+            anyhow::bail!("capability '{}': {}", issue.capability_id, issue.kind);
+        }
+
+        Ok(records)
+    }
+
+    /// Read every `crates/*/meta.json`, turning per-entry problems into issues.
+    fn scan(&self) -> Result<(Vec<CapabilityRecord>, Vec<HealthIssue>)> {
         let mut records = Vec::new();
+        let mut issues = Vec::new();
 
         // Look in the crates/ subdirectory
         let crates_dir = self.root.join("crates");
@@ -54,7 +93,7 @@ impl CapabilityRegistry {
             Ok(e) => e,
             Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
                 // No crates directory yet – return empty.
-                return Ok(records);
+                return Ok((records, issues));
             }
             Err(err) => {
                 return Err(err).context(format!(
@@ -70,29 +109,415 @@ impl CapabilityRegistry {
             if !path.is_dir() {
                 continue;
             }
+            let dir_name = entry.file_name().to_string_lossy().into_owned();
 
             let meta_path = path.join("meta.json");
             if !meta_path.exists() {
+                // Library crates (like common/) legitimately have no meta.json.
+                if path.join("src").join("main.rs").exists() {
+                    issues.push(HealthIssue::new(&dir_name, IssueKind::MissingMeta));
+                }
                 continue;
             }
 
-            let data = fs::read_to_string(&meta_path)
-                .with_context(|| format!("failed to read {:?}", meta_path))?;
-            let meta: CapabilityMeta = serde_json::from_str(&data)
-                .with_context(|| format!("failed to parse {:?}", meta_path))?;
-
-            let record = CapabilityRecord {
-                id: meta.id,
-                summary: meta.summary,
-                embedding: meta.embedding,
-                binary: meta.binary,
-                status: meta.status,
-                replaced_by: meta.replaced_by,
+            let meta = match read_meta_value(&meta_path) {
+                Ok(meta) => meta,
+                Err(err) => {
+                    issues.push(HealthIssue::new(
+                        &dir_name,
+                        IssueKind::MalformedMeta(format!("{:#}", err)),
+                    ));
+                    continue;
+                }
             };
+            let id = meta
+                .get("id")
+                .and_then(|v| v.as_str())
+                .unwrap_or(&dir_name)
+                .to_string();
 
-            records.push(record);
+            if let Some(status) = meta.get("status") {
+                if serde_json::from_value::<CapabilityStatus>(status.clone()).is_err() {
+                    let status = status
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| status.to_string());
+                    issues.push(HealthIssue::new(&id, IssueKind::UnknownStatus(status)));
+                    continue;
+                }
+            }
+
+            let meta: CapabilityMeta = match serde_json::from_value(meta) {
+                Ok(meta) => meta,
+                Err(err) => {
+                    issues.push(HealthIssue::new(
+                        &id,
+                        IssueKind::MalformedMeta(err.to_string()),
+                    ));
+                    continue;
+                }
+            };
+
+            if meta.id != dir_name {
+                issues.push(HealthIssue::new(
+                    &meta.id,
+                    IssueKind::IdMismatch { dir: dir_name },
+                ));
+            }
+
+            records.push(meta.into());
         }
 
-        Ok(records)
+        Ok((records, issues))
+    }
+
+    fn crate_dir(&self, id: &str) -> PathBuf {
+        self.root.join("crates").join(id)
+    }
+
+    fn append_history(&self, event: &RegistryEvent) -> Result<()> {
+        let path = self.crate_dir(&event.capability_id).join(HISTORY_FILE);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {:?}", path))?;
+        writeln!(file, "{}", serde_json::to_string(event)?)
+            .with_context(|| format!("failed to write {:?}", path))?;
+        Ok(())
+    }
+}
+
+impl From<CapabilityMeta> for CapabilityRecord {
+    fn from(meta: CapabilityMeta) -> Self {
+        Self {
+            id: meta.id,
+            summary: meta.summary,
+            embedding: meta.embedding,
+            binary: meta.binary,
+            status: meta.status,
+            replaced_by: meta.replaced_by,
+            deprecated_reason: meta.deprecated_reason,
+            input_schema: meta.input_schema,
+            output_schema: meta.output_schema,
+            tags: meta.tags,
+            example_tasks: meta.example_tasks,
+        }
+    }
+}
+
+impl From<&CapabilityRecord> for CapabilityMeta {
+    fn from(record: &CapabilityRecord) -> Self {
+        Self {
+            id: record.id.clone(),
+            summary: record.summary.clone(),
+            embedding: record.embedding.clone(),
+            binary: record.binary.clone(),
+            status: record.status.clone(),
+            replaced_by: record.replaced_by.clone(),
+            deprecated_reason: record.deprecated_reason.clone(),
+            input_schema: record.input_schema.clone(),
+            output_schema: record.output_schema.clone(),
+            tags: record.tags.clone(),
+            example_tasks: record.example_tasks.clone(),
+        }
+    }
+}
+
+fn read_meta_value(meta_path: &Path) -> Result<serde_json::Value> {
+    let data =
+        fs::read_to_string(meta_path).with_context(|| format!("failed to read {:?}", meta_path))?;
+    serde_json::from_str(&data).with_context(|| format!("failed to parse {:?}", meta_path))
+}
+
+fn read_meta(meta_path: &Path) -> Result<CapabilityMeta> {
+    let data =
+        fs::read_to_string(meta_path).with_context(|| format!("failed to read {:?}", meta_path))?;
+    serde_json::from_str(&data).with_context(|| format!("failed to parse {:?}", meta_path))
+}
+
+/// Filesystem backend: metadata in `crates/<id>/meta.json`, history in
+/// `crates/<id>/history.jsonl`, run stats in `run_stats.json`. Embeddings
+/// aren't cached.
+impl RegistryBackend for CapabilityRegistry {
+    fn load(&self) -> Result<Vec<CapabilityRecord>> {
+        self.load_capabilities()
+    }
+
+    fn load_checked(&self) -> Result<(Vec<CapabilityRecord>, Vec<HealthIssue>)> {
+        self.scan()
+    }
+
+    fn get(&self, id: &str) -> Result<Option<CapabilityRecord>> {
+        let meta_path = self.crate_dir(id).join("meta.json");
+        if !meta_path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(read_meta(&meta_path)?.into()))
+    }
+
+    fn upsert(&self, record: &CapabilityRecord) -> Result<()> {
+        let dir = self.crate_dir(&record.id);
+        if !dir.is_dir() {
+            anyhow::bail!(
+                "capability directory {:?} does not exist for '{}'",
+                dir,
+                record.id
+            );
+        }
+
+        // Via Value so keys are written sorted, like the rest of the meta.json files.
+        let meta = serde_json::to_value(CapabilityMeta::from(record))?;
+        fs::write(
+            dir.join("meta.json"),
+            serde_json::to_string_pretty(&meta)? + "\n",
+        )?;
+
+        self.append_history(&RegistryEvent::now(
+            &record.id,
+            RegistryChange::Upserted,
+            None,
+            None,
+        ))
+    }
+
+    fn set_status(
+        &self,
+        id: &str,
+        status: CapabilityStatus,
+        allowed: &dyn Fn(&CapabilityStatus) -> bool,
+        replaced_by: Option<&str>,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> Result<CapabilityStatus> {
+        let meta_path = self.crate_dir(id).join("meta.json");
+        if !meta_path.exists() {
+            anyhow::bail!("Capability '{}' not found", id);
+        }
+
+        // Edit the raw JSON so fields this struct doesn't know about survive.
+        let content = fs::read_to_string(&meta_path)?;
+        let mut meta: serde_json::Value = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse {:?}", meta_path))?;
+
+        let from: CapabilityStatus = meta
+            .get("status")
+            .map(|s| serde_json::from_value(s.clone()))
+            .transpose()?
+            .unwrap_or_default();
+        if !allowed(&from) {
+            return Err(transition_refused(id, &from, &status));
+        }
+
+        meta["status"] = json!(status);
+        if status == CapabilityStatus::Active {
+            if let Some(obj) = meta.as_object_mut() {
+                obj.remove("replaced_by");
+                obj.remove("deprecated_reason");
+            }
+        } else if let Some(replacement) = replaced_by {
+            meta["replaced_by"] = json!(replacement);
+        }
+        if let (CapabilityStatus::Deprecated, Some(reason)) = (&status, reason) {
+            meta["deprecated_reason"] = json!(reason);
+        }
+
+        fs::write(&meta_path, serde_json::to_string_pretty(&meta)? + "\n")?;
+
+        self.append_history(&RegistryEvent::now(
+            id,
+            RegistryChange::StatusChanged {
+                from: from.clone(),
+                to: status,
+                replaced_by: replaced_by.map(|r| r.to_string()),
+            },
+            Some(actor),
+            reason,
+        ))?;
+        Ok(from)
+    }
+
+    fn remove(&self, id: &str, actor: &str, reason: Option<&str>) -> Result<()> {
+        let meta_path = self.crate_dir(id).join("meta.json");
+        if !meta_path.exists() {
+            anyhow::bail!("Capability '{}' not found", id);
+        }
+
+        fs::remove_file(&meta_path).with_context(|| format!("failed to remove {:?}", meta_path))?;
+        self.append_history(&RegistryEvent::now(
+            id,
+            RegistryChange::Removed,
+            Some(actor),
+            reason,
+        ))
+    }
+
+    fn history(&self, id: &str) -> Result<Vec<RegistryEvent>> {
+        let path = self.crate_dir(id).join(HISTORY_FILE);
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err).with_context(|| format!("failed to read {:?}", path)),
+        };
+
+        data.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("failed to parse {:?} line {}", path, i + 1))
+            })
+            .collect()
+    }
+    fn run_stats(&self) -> Result<HashMap<String, CapabilityRunStats>> {
+        run_stats::load_file(&self.root.join(RUN_STATS_FILE))
+    }
+
+    fn record_run(&self, id: &str, success: bool, latency_ms: u64) -> Result<CapabilityRunStats> {
+        let path = self.root.join(RUN_STATS_FILE);
+        let mut stats = run_stats::load_file(&path)?;
+        let entry = stats.entry(id.to_string()).or_default();
+        entry.record(success, latency_ms);
+        let updated = entry.clone();
+        run_stats::save_file(&path, &stats)?;
+        Ok(updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fs_status_change_keeps_unknown_fields_and_logs_history() {
+        let root = std::env::temp_dir().join(format!("se_registry_{}", std::process::id()));
+        let dir = root.join("crates").join("get_car_details");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("meta.json"),
+            r#"{"id": "get_car_details", "summary": "Returns car details.", "owner": "fleet"}"#,
+        )
+        .unwrap();
+
+        let registry = CapabilityRegistry::new(&root);
+        registry
+            .set_status(
+                "get_car_details",
+                CapabilityStatus::Deprecated,
+                &|_| true,
+                None,
+                "test",
+                Some("broken"),
+            )
+            .unwrap();
+
+        let record = registry.get("get_car_details").unwrap().unwrap();
+        assert_eq!(record.status, CapabilityStatus::Deprecated);
+
+        let written = fs::read_to_string(dir.join("meta.json")).unwrap();
+        assert!(written.ends_with("}\n"));
+        let raw: serde_json::Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(raw["owner"], "fleet");
+        assert_eq!(raw["deprecated_reason"], "broken");
+
+        assert!(registry
+            .set_status(
+                "get_car_details",
+                CapabilityStatus::Retired,
+                &|from| *from == CapabilityStatus::Active,
+                None,
+                "test",
+                None,
+            )
+            .is_err());
+
+        let history = registry.history("get_car_details").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].reason.as_deref(), Some("broken"));
+        assert!(registry.get("missing").unwrap().is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_load_checked_reports_bad_entries() {
+        let root = std::env::temp_dir().join(format!("se_registry_check_{}", std::process::id()));
+        let crates = root.join("crates");
+        let write = |dir: &str, meta: Option<&str>, main: bool| {
+            let path = crates.join(dir);
+            fs::create_dir_all(path.join("src")).unwrap();
+            if let Some(meta) = meta {
+                fs::write(path.join("meta.json"), meta).unwrap();
+            }
+            let file = if main { "main.rs" } else { "lib.rs" };
+            fs::write(path.join("src").join(file), "").unwrap();
+        };
+        write("good", Some(r#"{"id": "good", "summary": "ok"}"#), true);
+        write("broken", Some("{not json"), true);
+        write(
+            "odd_status",
+            Some(r#"{"id": "odd_status", "summary": "x", "status": "zombie"}"#),
+            true,
+        );
+        write(
+            "renamed",
+            Some(r#"{"id": "original", "summary": "x"}"#),
+            true,
+        );
+        write("no_meta", None, true);
+        write("common", None, false);
+
+        let registry = CapabilityRegistry::new(&root);
+        let (records, issues) = registry.load_checked().unwrap();
+
+        let mut ids: Vec<&str> = records.iter().map(|r| r.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["good", "original"]);
+
+        let kinds: Vec<(&str, &IssueKind)> = issues
+            .iter()
+            .map(|i| (i.capability_id.as_str(), &i.kind))
+            .collect();
+        assert_eq!(kinds.len(), 4);
+        assert!(kinds.contains(&("no_meta", &IssueKind::MissingMeta)));
+        assert!(kinds.contains(&(
+            "odd_status",
+            &IssueKind::UnknownStatus("zombie".to_string())
+        )));
+        assert!(kinds.contains(&(
+            "original",
+            &IssueKind::IdMismatch {
+                dir: "renamed".to_string()
+            }
+        )));
+        assert!(issues
+            .iter()
+            .any(|i| i.capability_id == "broken" && matches!(i.kind, IssueKind::MalformedMeta(_))));
+
+        // The strict loader still refuses a malformed registry.
+        assert!(registry.load_capabilities().is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_fs_run_stats_persist() {
+        let root = std::env::temp_dir().join(format!("se_registry_stats_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+
+        let registry = CapabilityRegistry::new(&root);
+        registry.record_run("get_salary_details", true, 40).unwrap();
+        let updated = registry
+            .record_run("get_salary_details", false, 20)
+            .unwrap();
+        assert_eq!(updated.runs(), 2);
+
+        let stats = CapabilityRegistry::new(&root).run_stats().unwrap();
+        let s = &stats["get_salary_details"];
+        assert_eq!(s.runs(), 2);
+        assert_eq!(s.mean_latency_ms(), Some(30.0));
+        assert!(s.last_used.is_some());
+
+        fs::remove_dir_all(&root).unwrap();
     }
 }
@@ -331,7 +331,6 @@ impl CapabilityRunner {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::CapabilityStatus;
 
     #[test]
     fn test_run_echo_capability() {
@@ -340,12 +339,8 @@ mod tests {
         let runner = CapabilityRunner::new("capabilities").unwrap();
 
         let cap = CapabilityRecord {
-            id: "echo_rust".to_string(),
-            summary: "echo".to_string(),
-            embedding: None,
             binary: Some("../../target/wasm32-wasip1/release/echo_rust.wasm".to_string()),
-            status: CapabilityStatus::Active,
-            replaced_by: None,
+            ..CapabilityRecord::new("echo_rust", "echo")
         };
 
         let input = r#"{"message": "hello world"}"#;
@@ -0,0 +1,510 @@
+// crates/core/src/conversation.rs
+
+//! Message history of an agent run.
+//!
+//! A `Conversation` starts with a preamble (the system prompt and the task)
+//! followed by turns: an assistant message and the results of the tool calls
+//! it made, or a user follow-up. Trimming drops whole turns, so a tool result
+//! is never sent without the call it answers.
+//!
+//! Long runs are kept inside the model's context window by a
+//! `ContextPolicy`: tool outputs are capped as they arrive (`OutputLimit`),
+//! and `compact` summarizes older tool outputs and drops the oldest turns
+//! once the estimated size goes over budget. The preamble is never touched.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::ai_client::{ChatRequest, Message, ToolCall};
+
+/// Marks tool outputs and arguments that `compact` has already shortened.
+const SUMMARY_MARKER: &str = "[summarized]";
+/// Older tool-call arguments longer than this are shortened by `compact`.
+const MAX_OLD_ARGUMENT_CHARS: usize = 1000;
+
+/// Rough token count of a text: about four characters per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Which part of an over-long tool output to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Head,
+    Tail,
+    /// Half from each end; build logs have the first error near the top and
+    /// the outcome at the bottom.
+    HeadAndTail,
+}
+
+/// Cap on the size of a tool output when it's added to the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputLimit {
+    pub max_chars: usize,
+    pub keep: Keep,
+}
+
+impl OutputLimit {
+    pub fn new(max_chars: usize, keep: Keep) -> Self {
+        Self { max_chars, keep }
+    }
+
+    /// Shorten `text` to `max_chars`, noting how much was cut.
+    pub fn apply(&self, text: &str) -> String {
+        let total = text.chars().count();
+        if total <= self.max_chars {
+            return text.to_string();
+        }
+
+        let cut = total - self.max_chars;
+        let marker = |n: usize| format!("\n... [{} chars truncated] ...\n", n);
+        let head = |n: usize| text.chars().take(n).collect::<String>();
+        let tail = |n: usize| text.chars().skip(total - n).collect::<String>();
+
+        match self.keep {
+            Keep::Head => head(self.max_chars) + &marker(cut),
+            Keep::Tail => marker(cut) + &tail(self.max_chars),
+            Keep::HeadAndTail => {
+                let half = self.max_chars / 2;
+                head(half) + &marker(cut) + &tail(self.max_chars - half)
+            }
+        }
+    }
+}
+
+/// How much history a loop may send.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContextPolicy {
+    /// Estimated prompt tokens to stay under.
+    pub max_tokens: usize,
+    /// Latest turns whose tool outputs are never summarized.
+    pub keep_recent_turns: usize,
+    /// Limit for outputs of tools without their own.
+    pub default_output_limit: OutputLimit,
+}
+
+impl Default for ContextPolicy {
+    fn default() -> Self {
+        Self {
+            max_tokens: 48_000,
+            keep_recent_turns: 3,
+            default_output_limit: OutputLimit::new(16_000, Keep::HeadAndTail),
+        }
+    }
+}
+
+impl ContextPolicy {
+    /// Read the policy from environment variables named `<prefix>_...`.
+    ///
+    /// - `<prefix>_CONTEXT_TOKENS` (default: 48000)
+    /// - `<prefix>_KEEP_TURNS` (default: 3)
+    /// - `<prefix>_TOOL_OUTPUT_CHARS` (default: 16000)
+    pub fn from_env(prefix: &str) -> Result<Self> {
+        let read = |name: &str, default: usize| -> Result<usize> {
+            let var = format!("{}_{}", prefix, name);
+            match std::env::var(&var) {
+                Ok(v) => v
+                    .parse()
+                    .with_context(|| format!("invalid {} '{}'", var, v)),
+                Err(_) => Ok(default),
+            }
+        };
+
+        let defaults = Self::default();
+        Ok(Self {
+            max_tokens: read("CONTEXT_TOKENS", defaults.max_tokens)?,
+            keep_recent_turns: read("KEEP_TURNS", defaults.keep_recent_turns)?,
+            default_output_limit: OutputLimit::new(
+                read("TOOL_OUTPUT_CHARS", defaults.default_output_limit.max_chars)?,
+                defaults.default_output_limit.keep,
+            ),
+        })
+    }
+}
+
+/// What `compact` changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Compaction {
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+    /// Tool outputs and arguments replaced by a summary.
+    pub summarized: usize,
+    /// Messages dropped with their turns.
+    pub dropped: usize,
+}
+
+impl Compaction {
+    pub fn changed(&self) -> bool {
+        self.summarized > 0 || self.dropped > 0
+    }
+}
+
+/// A short stand-in for an old tool output: its size, first line and any
+/// lines that look like errors.
+fn summarize_output(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let clip = |line: &str| line.chars().take(200).collect::<String>();
+
+    let mut summary = format!(
+        "{} {} lines, {} chars",
+        SUMMARY_MARKER,
+        lines.len(),
+        text.chars().count()
+    );
+    if let Some(first) = lines.first() {
+        summary.push_str(&format!(": {}", clip(first)));
+    }
+    let notable = lines.iter().skip(1).filter(|line| {
+        let lower = line.to_lowercase();
+        lower.contains("error") || lower.contains("panicked") || lower.contains("failed")
+    });
+    for line in notable.take(5) {
+        summary.push_str(&format!("\n{}", clip(line)));
+    }
+    summary
+}
+
+/// Estimated tokens of one message, including a little per-message overhead.
+fn message_tokens(message: &Message) -> usize {
+    let calls = match message {
+        Message::Assistant { tool_calls, .. } => tool_calls
+            .iter()
+            .map(|c| estimate_tokens(&c.function.name) + estimate_tokens(&c.function.arguments))
+            .sum(),
+        _ => 0,
+    };
+    4 + calls + message.content().map_or(0, estimate_tokens)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Conversation {
+    messages: Vec<Message>,
+}
+
+impl Conversation {
+    /// Start with a system prompt and the user's task.
+    pub fn new(system_prompt: impl Into<String>, task: impl Into<String>) -> Self {
+        Self {
+            messages: vec![Message::system(system_prompt), Message::user(task)],
+        }
+    }
+
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// Add the result of a tool call.
+    pub fn push_tool_result(&mut self, call: &ToolCall, content: impl Into<String>) {
+        self.messages.push(Message::tool_result(call, content));
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// A request carrying the whole history.
+    pub fn request(&self) -> ChatRequest {
+        ChatRequest::new(self.messages.clone())
+    }
+
+    /// Number of leading messages that are never trimmed: system messages
+    /// and the first user message.
+    fn preamble_len(&self) -> usize {
+        let system = self
+            .messages
+            .iter()
+            .take_while(|m| matches!(m, Message::System { .. }))
+            .count();
+        match self.messages.get(system) {
+            Some(Message::User { .. }) => system + 1,
+            _ => system,
+        }
+    }
+
+    /// Start index of each turn after the preamble.
+    fn turn_starts(&self) -> Vec<usize> {
+        (self.preamble_len()..self.messages.len())
+            .filter(|&i| !matches!(self.messages[i], Message::Tool(_)))
+            .collect()
+    }
+
+    /// Drop the oldest turns until at most `max_messages` remain, keeping
+    /// the preamble and the latest turn. Returns how many messages were
+    /// dropped.
+    pub fn trim(&mut self, max_messages: usize) -> usize {
+        let preamble = self.preamble_len();
+        let starts = self.turn_starts();
+
+        // Cut at the first turn boundary that fits, but never past the last turn.
+        let excess = self.messages.len().saturating_sub(max_messages);
+        let cut = starts
+            .iter()
+            .copied()
+            .find(|&start| start - preamble >= excess)
+            .or(starts.last().copied())
+            .unwrap_or(preamble);
+
+        self.messages.drain(preamble..cut).count()
+    }
+
+    /// Estimated prompt tokens of the whole history.
+    pub fn estimated_tokens(&self) -> usize {
+        self.messages.iter().map(message_tokens).sum()
+    }
+
+    /// Fit the history into `policy.max_tokens`.
+    ///
+    /// Tool outputs and large tool-call arguments older than the last
+    /// `keep_recent_turns` turns are summarized first, oldest first; if
+    /// that's not enough, the oldest turns are dropped. The preamble and
+    /// the latest turn always stay.
+    pub fn compact(&mut self, policy: &ContextPolicy) -> Compaction {
+        let tokens_before = self.estimated_tokens();
+        let mut report = Compaction {
+            tokens_before,
+            tokens_after: tokens_before,
+            ..Compaction::default()
+        };
+        if tokens_before <= policy.max_tokens {
+            return report;
+        }
+
+        let starts = self.turn_starts();
+        let recent = starts
+            .len()
+            .checked_sub(policy.keep_recent_turns.max(1))
+            .map_or(self.preamble_len(), |i| starts[i]);
+
+        let mut tokens = tokens_before;
+        for i in self.preamble_len()..recent {
+            if tokens <= policy.max_tokens {
+                break;
+            }
+            let before = message_tokens(&self.messages[i]);
+            match &mut self.messages[i] {
+                Message::Tool(result) if !result.content.starts_with(SUMMARY_MARKER) => {
+                    result.content = summarize_output(&result.content);
+                    report.summarized += 1;
+                }
+                Message::Assistant { tool_calls, .. } => {
+                    for call in tool_calls.iter_mut() {
+                        let args = &call.function.arguments;
+                        if args.chars().count() > MAX_OLD_ARGUMENT_CHARS
+                            && !args.contains(SUMMARY_MARKER)
+                        {
+                            let head: String = args.chars().take(200).collect();
+                            call.function.arguments = serde_json::json!({
+                                "_summarized": format!(
+                                    "{} {} chars: {}",
+                                    SUMMARY_MARKER,
+                                    args.chars().count(),
+                                    head
+                                )
+                            })
+                            .to_string();
+                            report.summarized += 1;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            tokens = tokens + message_tokens(&self.messages[i]) - before;
+        }
+
+        while tokens > policy.max_tokens {
+            let starts = self.turn_starts();
+            if starts.len() < 2 {
+                break;
+            }
+            let dropped: usize = self.messages[starts[0]..starts[1]]
+                .iter()
+                .map(message_tokens)
+                .sum();
+            report.dropped += self.messages.drain(starts[0]..starts[1]).count();
+            tokens -= dropped;
+        }
+
+        report.tokens_after = tokens;
+        report
+    }
+
+    /// Write the history as JSON.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write conversation {}", path.display()))
+    }
+
+    /// Read a history written by `save`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("failed to read conversation {}", path.display()))?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("failed to parse conversation {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_client::ToolFunction;
+
+    fn call(id: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: ToolFunction {
+                name: "run_capability".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }
+    }
+
+    fn tool_turn(conversation: &mut Conversation, ids: &[&str]) {
+        let calls: Vec<ToolCall> = ids.iter().map(|id| call(id)).collect();
+        conversation.push(Message::Assistant {
+            content: None,
+            tool_calls: calls.clone(),
+        });
+        for c in &calls {
+            conversation.push_tool_result(c, format!("result of {}", c.id));
+        }
+    }
+
+    #[test]
+    fn test_trim_drops_whole_turns() {
+        let mut conversation = Conversation::new("rules", "task");
+        tool_turn(&mut conversation, &["a", "b"]);
+        tool_turn(&mut conversation, &["c"]);
+        conversation.push(Message::user("keep going"));
+        tool_turn(&mut conversation, &["d", "e"]);
+        assert_eq!(conversation.len(), 11);
+
+        // Dropping 3 messages would split the first turn; the whole
+        // turn goes instead.
+        assert_eq!(conversation.trim(8), 3);
+        assert_eq!(conversation.len(), 8);
+        assert!(matches!(
+            conversation.messages()[2],
+            Message::Assistant { ref tool_calls, .. } if tool_calls[0].id == "c"
+        ));
+
+        // The preamble and the latest turn always stay.
+        assert_eq!(conversation.trim(0), 3);
+        assert_eq!(conversation.messages()[1].content(), Some("task"));
+        assert!(matches!(
+            conversation.messages()[2],
+            Message::Assistant { ref tool_calls, .. } if tool_calls[0].id == "d"
+        ));
+        assert_eq!(conversation.len(), 5);
+        assert_eq!(conversation.trim(0), 0);
+    }
+
+    #[test]
+    fn test_output_limits() {
+        let text = "0123456789";
+        assert_eq!(OutputLimit::new(20, Keep::Head).apply(text), text);
+        assert_eq!(
+            OutputLimit::new(4, Keep::Head).apply(text),
+            "0123\n... [6 chars truncated] ...\n"
+        );
+        assert_eq!(
+            OutputLimit::new(4, Keep::Tail).apply(text),
+            "\n... [6 chars truncated] ...\n6789"
+        );
+        assert_eq!(
+            OutputLimit::new(4, Keep::HeadAndTail).apply("héllo wörld"),
+            "hé\n... [7 chars truncated] ...\nld"
+        );
+    }
+
+    #[test]
+    fn test_compact_summarizes_old_outputs_then_drops_turns() {
+        let log = |id: &str| {
+            format!(
+                "Compiling {}\n{}error[E0308]: mismatched types\n",
+                id,
+                "warning: unused\n".repeat(200)
+            )
+        };
+        let mut conversation = Conversation::new("rules", "task");
+        for id in ["a", "b", "c", "d"] {
+            let mut write = call(id);
+            write.function.arguments = format!(r#"{{"content": "{}"}}"#, "x".repeat(4000));
+            conversation.push(Message::Assistant {
+                content: None,
+                tool_calls: vec![write.clone()],
+            });
+            conversation.push_tool_result(&write, log(id));
+        }
+        let before = conversation.estimated_tokens();
+
+        let unchanged = conversation.compact(&ContextPolicy {
+            max_tokens: before,
+            ..ContextPolicy::default()
+        });
+        assert!(!unchanged.changed());
+
+        let policy = ContextPolicy {
+            max_tokens: before * 3 / 5,
+            keep_recent_turns: 2,
+            ..ContextPolicy::default()
+        };
+        let report = conversation.compact(&policy);
+        assert_eq!(report.dropped, 0);
+        assert!(report.summarized >= 2);
+        assert!(report.tokens_after <= policy.max_tokens);
+        assert_eq!(report.tokens_after, conversation.estimated_tokens());
+
+        let summary = conversation.messages()[3].content().unwrap();
+        assert!(summary.starts_with("[summarized] 202 lines"));
+        assert!(summary.contains("Compiling a"));
+        assert!(summary.contains("error[E0308]"));
+        // The latest turns are intact.
+        assert_eq!(
+            conversation.messages()[9].content(),
+            Some(log("d").as_str())
+        );
+
+        // Summaries alone can't get this small, so old turns go.
+        let report = conversation.compact(&ContextPolicy {
+            max_tokens: 3000,
+            keep_recent_turns: 2,
+            ..ContextPolicy::default()
+        });
+        assert!(report.dropped > 0);
+        assert_eq!(conversation.messages()[0].content(), Some("rules"));
+        assert_eq!(conversation.messages()[1].content(), Some("task"));
+        assert!(matches!(
+            conversation.messages()[2],
+            Message::Assistant { .. }
+        ));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut conversation = Conversation::new("rules", "task");
+        tool_turn(&mut conversation, &["a"]);
+        conversation.push(Message::assistant("done"));
+
+        let path =
+            std::env::temp_dir().join(format!("se_conversation_{}.json", std::process::id()));
+        conversation.save(&path).unwrap();
+        let loaded = Conversation::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, conversation);
+        assert_eq!(loaded.request().messages.len(), 5);
+    }
+}
@@ -1,39 +1,137 @@
 // crates/core/src/embedding.rs
 
 use anyhow::{Context, Result};
+use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 
+use crate::local_embedder::LocalEmbedder;
+use crate::ollama_embedder::OllamaEmbedder;
+use crate::openai_embedder::OpenAiEmbedder;
+use crate::retry::{check_status, RetryPolicy, Retrying};
+
 /// Abstract embedding provider.
 ///
 /// Implementations can use Microsoft AI Foundry (Azure OpenAI), local models, etc.
-/// For now we only provide a MicrosoftFoundryEmbedder.
+/// See `embedder_from_env` for the providers that ship with the runtime.
 pub trait Embedder {
     fn embed(&self, text: &str) -> Result<Vec<f32>>;
+
+    /// Embed several texts, returning one vector per input in the same order.
+    ///
+    /// The default implementation calls `embed` once per text; providers with
+    /// a native batch endpoint should override it.
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        texts.iter().map(|t| self.embed(t)).collect()
+    }
+
+    /// Identifies the vector space this embedder produces (provider and
+    /// model), so vectors cached from another model aren't reused. `None`
+    /// disables caching.
+    fn model_id(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<E: Embedder + ?Sized> Embedder for Box<E> {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        (**self).embed(text)
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        (**self).embed_batch(texts)
+    }
+
+    fn model_id(&self) -> Option<String> {
+        (**self).model_id()
+    }
+}
+
+/// Construct the embedder selected by the EMBEDDING_PROVIDER environment variable.
+///
+/// - "foundry" (default): MicrosoftFoundryEmbedder, needs the FOUNDRY_* variables
+/// - "openai": OpenAiEmbedder, any OpenAI-compatible `/v1/embeddings` server
+/// - "ollama": OllamaEmbedder, a local Ollama instance
+/// - "local": LocalEmbedder, fully offline and needs no credentials
+///
+/// Network embedders retry transient failures per `RetryPolicy::from_env`.
+pub fn embedder_from_env() -> Result<Box<dyn Embedder>> {
+    let provider = std::env::var("EMBEDDING_PROVIDER").unwrap_or_else(|_| "foundry".to_string());
+
+    let retry = RetryPolicy::from_env()?;
+
+    match provider.trim().to_lowercase().as_str() {
+        "foundry" | "azure" => Ok(Box::new(Retrying::new(
+            MicrosoftFoundryEmbedder::from_env()?,
+            retry,
+        ))),
+        "openai" => Ok(Box::new(Retrying::new(OpenAiEmbedder::from_env()?, retry))),
+        "ollama" => Ok(Box::new(Retrying::new(OllamaEmbedder::from_env()?, retry))),
+        "local" => Ok(Box::new(LocalEmbedder::from_env()?)),
+        other => anyhow::bail!(
+            "unknown EMBEDDING_PROVIDER '{}' (expected 'foundry', 'openai', 'ollama' or 'local')",
+            other
+        ),
+    }
 }
 
+/// Default max inputs per Foundry request (the limit of older Azure deployments).
+const DEFAULT_FOUNDRY_BATCH_SIZE: usize = 16;
+
 /// Embedding client for Microsoft AI Foundry (Azure OpenAI).
 ///
 /// Expects the following environment variables:
 ///
 /// - FOUNDRY_ENDPOINT
-///     e.g. "https://myresource.openai.azure.com"
+///   e.g. "https://myresource.openai.azure.com"
 ///
 /// - FOUNDRY_EMBED_DEPLOYMENT
-///     e.g. "text-embedding-3-small"
+///   e.g. "text-embedding-3-small"
 ///
 /// - FOUNDRY_API_KEY
-///     your Azure OpenAI / Foundry API key
+///   your Azure OpenAI / Foundry API key
 ///
 /// - FOUNDRY_API_VERSION (optional)
-///     default: "2024-02-15-preview"
+///   default: "2024-02-15-preview"
+///
+/// - FOUNDRY_EMBED_BATCH_SIZE (optional)
+///   max inputs per request in `embed_batch`, default: 16
 pub struct MicrosoftFoundryEmbedder {
-    endpoint: String,
+    client: Client,
+    url: String,
     deployment: String,
     api_key: String,
-    api_version: String,
+    batch_size: usize,
 }
 
 impl MicrosoftFoundryEmbedder {
+    /// Construct with explicit parameters.
+    pub fn new(endpoint: &str, deployment: &str, api_key: &str, api_version: Option<&str>) -> Self {
+        let api_version = api_version.unwrap_or("2024-02-15-preview");
+
+        // Azure / Foundry embedding endpoint shape:
+        // POST {endpoint}/openai/deployments/{deployment}/embeddings?api-version={version}
+        let url = format!(
+            "{}/openai/deployments/{}/embeddings?api-version={}",
+            endpoint.trim_end_matches('/'),
+            deployment,
+            api_version,
+        );
+
+        Self {
+            client: Client::new(),
+            url,
+            deployment: deployment.to_string(),
+            api_key: api_key.to_string(),
+            batch_size: DEFAULT_FOUNDRY_BATCH_SIZE,
+        }
+    }
+
+    /// Set the max number of inputs sent in one request.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
     /// Construct from environment variables.
     pub fn from_env() -> Result<Self> {
         let endpoint = std::env::var("FOUNDRY_ENDPOINT").context("FOUNDRY_ENDPOINT not set")?;
@@ -46,74 +144,95 @@ impl MicrosoftFoundryEmbedder {
         let api_version = std::env::var("FOUNDRY_API_VERSION")
             .unwrap_or_else(|_| "2024-02-15-preview".to_string());
 
-        Ok(Self {
-            endpoint,
-            deployment,
-            api_key,
-            api_version,
-        })
+        let batch_size = match std::env::var("FOUNDRY_EMBED_BATCH_SIZE") {
+            Ok(v) => v
+                .parse::<usize>()
+                .with_context(|| format!("FOUNDRY_EMBED_BATCH_SIZE is not a number: {}", v))?,
+            Err(_) => DEFAULT_FOUNDRY_BATCH_SIZE,
+        };
+
+        Ok(
+            Self::new(&endpoint, &deployment, &api_key, Some(&api_version))
+                .with_batch_size(batch_size),
+        )
+    }
+
+    /// Embed one chunk of inputs in a single request.
+    fn embed_chunk(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let body = FoundryEmbeddingRequest { input: texts };
+
+        let resp = self
+            .client
+            .post(&self.url)
+            .header("api-key", &self.api_key)
+            .json(&body)
+            .send()
+            .context("failed to send Microsoft Foundry embedding request")?;
+
+        let resp = check_status(resp, "Microsoft Foundry embeddings")?;
+
+        let parsed: FoundryEmbeddingResponse = resp
+            .json()
+            .context("failed to parse Microsoft Foundry embeddings response JSON")?;
+
+        ordered_embeddings(parsed.data, texts.len())
+            .context("Microsoft Foundry embeddings response did not match the request")
     }
 }
 
 #[derive(Debug, Serialize)]
 struct FoundryEmbeddingRequest<'a> {
-    input: &'a str,
+    input: &'a [&'a str],
 }
 
 #[derive(Debug, Deserialize)]
 struct FoundryEmbeddingResponse {
-    data: Vec<FoundryEmbeddingData>,
+    data: Vec<EmbeddingData>,
 }
 
+/// One entry of an OpenAI-style `data` array.
 #[derive(Debug, Deserialize)]
-struct FoundryEmbeddingData {
+pub(crate) struct EmbeddingData {
+    #[serde(default)]
+    index: Option<usize>,
     embedding: Vec<f32>,
 }
 
-impl Embedder for MicrosoftFoundryEmbedder {
-    fn embed(&self, text: &str) -> Result<Vec<f32>> {
-        let client = reqwest::blocking::Client::new();
-
-        // Azure / Foundry embedding endpoint shape:
-        // POST {endpoint}/openai/deployments/{deployment}/embeddings?api-version={version}
-        let url = format!(
-            "{}/openai/deployments/{}/embeddings?api-version={}",
-            self.endpoint.trim_end_matches('/'),
-            self.deployment,
-            self.api_version,
-        );
-
-        let body = FoundryEmbeddingRequest { input: text };
-
-        let resp = client
-            .post(&url)
-            .header("api-key", &self.api_key)
-            .json(&body)
-            .send()
-            .context("failed to send Microsoft Foundry embedding request")?;
+/// Put OpenAI-style embedding results back in input order.
+///
+/// The API returns an `index` per entry and doesn't promise ordering.
+pub(crate) fn ordered_embeddings(
+    mut data: Vec<EmbeddingData>,
+    expected: usize,
+) -> Result<Vec<Vec<f32>>> {
+    if data.len() != expected {
+        anyhow::bail!("expected {} embeddings, got {}", expected, data.len());
+    }
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text_body = resp
-                .text()
-                .unwrap_or_else(|_| "<failed to read error body>".to_string());
-            anyhow::bail!(
-                "Microsoft Foundry embeddings request failed: HTTP {} - {}",
-                status,
-                text_body
-            );
-        }
+    if data.iter().all(|d| d.index.is_some()) {
+        data.sort_by_key(|d| d.index);
+    }
 
-        let parsed: FoundryEmbeddingResponse = resp
-            .json()
-            .context("failed to parse Microsoft Foundry embeddings response JSON")?;
+    Ok(data.into_iter().map(|d| d.embedding).collect())
+}
 
-        let first = parsed
-            .data
+impl Embedder for MicrosoftFoundryEmbedder {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_chunk(&[text])?
             .into_iter()
             .next()
-            .context("Microsoft Foundry embeddings response contained no data")?;
+            .context("Microsoft Foundry embeddings response contained no data")
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut out = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(self.batch_size) {
+            out.extend(self.embed_chunk(chunk)?);
+        }
+        Ok(out)
+    }
 
-        Ok(first.embedding)
+    fn model_id(&self) -> Option<String> {
+        Some(format!("foundry:{}", self.deployment))
     }
 }
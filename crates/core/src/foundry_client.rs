@@ -1,35 +1,43 @@
 // crates/core/src/foundry_client.rs
 
 use anyhow::{Context, Result};
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::Serialize;
+use serde_json::{json, Value};
 
 use crate::ai_client::{AiClient, ChatRequest, ChatResponse};
+use crate::retry::check_status;
+use crate::streaming::{emit_response, read_stream, OnDelta};
+
+const DEFAULT_API_VERSION: &str = "2024-10-21";
 
 /// Chat client for Microsoft AI Foundry (Azure OpenAI).
 ///
 /// Expects the following environment variables:
 ///
 /// - FOUNDRY_ENDPOINT
-///     e.g. "https://myresource.openai.azure.com"
+///   e.g. "https://myresource.openai.azure.com"
 ///
 /// - FOUNDRY_CHAT_DEPLOYMENT
-///     e.g. "gpt-4o"
+///   e.g. "gpt-4o"
 ///
 /// - FOUNDRY_API_KEY
-///     your Azure OpenAI / Foundry API key
+///   your Azure OpenAI / Foundry API key
 ///
 /// - FOUNDRY_API_VERSION (optional)
-///     default: "2024-02-15-preview"
+///   default: "2024-10-21", the first GA version that reports usage when
+///   streaming
 pub struct FoundryClient {
     client: Client,
     url: String,
     api_key: String,
+    stream: bool,
 }
 
 impl FoundryClient {
     /// Construct with explicit parameters.
     pub fn new(endpoint: &str, deployment: &str, api_key: &str, api_version: Option<&str>) -> Self {
-        let api_version = api_version.unwrap_or("2024-02-15-preview");
+        let api_version = api_version.unwrap_or(DEFAULT_API_VERSION);
         let url = format!(
             "{}/openai/deployments/{}/chat/completions?api-version={}",
             endpoint.trim_end_matches('/'),
@@ -41,9 +49,23 @@ impl FoundryClient {
             client: Client::new(),
             url,
             api_key: api_key.to_string(),
+            stream: true,
         }
     }
 
+    /// Whether `chat_stream` asks for a streamed response (default: true).
+    pub fn with_streaming(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    fn post(&self, body: &FoundryChatRequest) -> RequestBuilder {
+        self.client
+            .post(&self.url)
+            .header("api-key", &self.api_key)
+            .json(body)
+    }
+
     /// Construct from environment variables using FOUNDRY_CHAT_DEPLOYMENT.
     pub fn from_env() -> Result<Self> {
         Self::from_env_with_deployment_var("FOUNDRY_CHAT_DEPLOYMENT")
@@ -54,15 +76,23 @@ impl FoundryClient {
     /// This allows using different models by specifying different env vars,
     /// e.g. `FOUNDRY_MUTATION_DEPLOYMENT` for a coding-focused model.
     pub fn from_env_with_deployment_var(deployment_var: &str) -> Result<Self> {
-        let endpoint = std::env::var("FOUNDRY_ENDPOINT").context("FOUNDRY_ENDPOINT not set")?;
+        Self::from_vars_with_deployment_var(deployment_var, |name| std::env::var(name).ok())
+    }
+
+    /// Construct from the same variables, looked up with `vars`.
+    pub fn from_vars_with_deployment_var(
+        deployment_var: &str,
+        vars: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let endpoint = vars("FOUNDRY_ENDPOINT").context("FOUNDRY_ENDPOINT not set")?;
 
         let deployment =
-            std::env::var(deployment_var).with_context(|| format!("{} not set", deployment_var))?;
+            vars(deployment_var).with_context(|| format!("{} not set", deployment_var))?;
 
-        let api_key = std::env::var("FOUNDRY_API_KEY").context("FOUNDRY_API_KEY not set")?;
+        let api_key = vars("FOUNDRY_API_KEY").context("FOUNDRY_API_KEY not set")?;
 
-        let api_version = std::env::var("FOUNDRY_API_VERSION")
-            .unwrap_or_else(|_| "2024-02-15-preview".to_string());
+        let api_version =
+            vars("FOUNDRY_API_VERSION").unwrap_or_else(|| DEFAULT_API_VERSION.to_string());
 
         Ok(Self::new(
             &endpoint,
@@ -73,27 +103,37 @@ impl FoundryClient {
     }
 }
 
+/// Request body: the shared request, optionally streamed.
+#[derive(Serialize)]
+struct FoundryChatRequest<'a> {
+    #[serde(flatten)]
+    request: &'a ChatRequest,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    /// Asks for a final chunk with token usage when streaming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<Value>,
+}
+
+impl<'a> FoundryChatRequest<'a> {
+    fn new(request: &'a ChatRequest, stream: bool) -> Self {
+        Self {
+            request,
+            stream,
+            stream_options: stream.then(|| json!({ "include_usage": true })),
+        }
+    }
+}
+
 impl AiClient for FoundryClient {
     fn chat(&self, request: ChatRequest) -> Result<ChatResponse> {
+        let body = FoundryChatRequest::new(&request, false);
         let resp = self
-            .client
-            .post(&self.url)
-            .header("api-key", &self.api_key)
-            .json(&request)
+            .post(&body)
             .send()
             .context("failed to send Foundry chat request")?;
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text_body = resp
-                .text()
-                .unwrap_or_else(|_| "<failed to read error body>".to_string());
-            anyhow::bail!(
-                "Foundry chat request failed: HTTP {} - {}",
-                status,
-                text_body
-            );
-        }
+        let resp = check_status(resp, "Foundry chat")?;
 
         let parsed: ChatResponse = resp
             .json()
@@ -101,4 +141,42 @@ impl AiClient for FoundryClient {
 
         Ok(parsed)
     }
+
+    fn chat_stream(&self, request: ChatRequest, on_delta: OnDelta<'_>) -> Result<ChatResponse> {
+        if !self.stream {
+            let response = self.chat(request)?;
+            emit_response(&response, on_delta);
+            return Ok(response);
+        }
+
+        let body = FoundryChatRequest::new(&request, true);
+        let resp = self
+            .post(&body)
+            .send()
+            .context("failed to send Foundry chat request")?;
+
+        let resp = check_status(resp, "Foundry chat")?;
+
+        read_stream(resp, "Foundry chat", on_delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_client::Message;
+
+    #[test]
+    fn test_request_body_asks_for_usage_when_streaming() {
+        let request = ChatRequest::new(vec![Message::user("hi")]);
+
+        let body = serde_json::to_value(FoundryChatRequest::new(&request, true)).unwrap();
+        assert_eq!(body["messages"][0]["content"], "hi");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["stream_options"]["include_usage"], true);
+
+        let body = serde_json::to_value(FoundryChatRequest::new(&request, false)).unwrap();
+        assert!(body.get("stream").is_none());
+        assert!(body.get("stream_options").is_none());
+    }
 }
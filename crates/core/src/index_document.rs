@@ -0,0 +1,202 @@
+// crates/core/src/index_document.rs
+
+use anyhow::Result;
+
+use crate::types::CapabilityRecord;
+
+/// Default fields included in the indexed document.
+const DEFAULT_FIELDS: &[DocumentField] = &[
+    DocumentField::Summary,
+    DocumentField::Fields,
+    DocumentField::Examples,
+    DocumentField::Tags,
+];
+
+/// A part of a capability that can go into its indexed document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentField {
+    /// The one-line summary.
+    Summary,
+    /// Top-level property names of the input and output schemas.
+    Fields,
+    /// Example tasks the capability has solved.
+    Examples,
+    /// Free-form tags.
+    Tags,
+}
+
+impl DocumentField {
+    fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "summary" => Ok(Self::Summary),
+            "fields" | "schema" => Ok(Self::Fields),
+            "examples" => Ok(Self::Examples),
+            "tags" => Ok(Self::Tags),
+            other => anyhow::bail!(
+                "unknown index document field '{}' (expected summary, fields, examples or tags)",
+                other
+            ),
+        }
+    }
+}
+
+/// What text gets embedded for each capability.
+///
+/// By default a capability is indexed as a single document combining its
+/// summary, input/output field names, example tasks and tags. With
+/// `multi_vector`, each example task is embedded as its own vector next to
+/// the main document and a capability scores as its best-matching vector,
+/// so a query phrased like a past task matches that task directly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexDocumentOptions {
+    /// Fields included, in document order.
+    pub fields: Vec<DocumentField>,
+    /// Embed example tasks as separate vectors instead of inline.
+    pub multi_vector: bool,
+}
+
+impl Default for IndexDocumentOptions {
+    fn default() -> Self {
+        Self {
+            fields: DEFAULT_FIELDS.to_vec(),
+            multi_vector: false,
+        }
+    }
+}
+
+impl IndexDocumentOptions {
+    /// Index the summary alone (the original behaviour).
+    pub fn summary_only() -> Self {
+        Self {
+            fields: vec![DocumentField::Summary],
+            multi_vector: false,
+        }
+    }
+
+    /// Read overrides from the environment, falling back to the defaults.
+    ///
+    /// - INDEX_DOCUMENT_FIELDS (default: "summary,fields,examples,tags")
+    /// - INDEX_MULTI_VECTOR (default: false)
+    pub fn from_env() -> Result<Self> {
+        let mut opts = Self::default();
+
+        if let Ok(v) = std::env::var("INDEX_DOCUMENT_FIELDS") {
+            opts.fields = v
+                .split(',')
+                .filter(|f| !f.trim().is_empty())
+                .map(DocumentField::parse)
+                .collect::<Result<_>>()?;
+        }
+        if let Ok(v) = std::env::var("INDEX_MULTI_VECTOR") {
+            opts.multi_vector = matches!(v.to_lowercase().as_str(), "1" | "true" | "yes");
+        }
+
+        if opts.fields.is_empty() {
+            anyhow::bail!("INDEX_DOCUMENT_FIELDS must name at least one field");
+        }
+
+        Ok(opts)
+    }
+
+    /// Texts to embed for a capability. The first is the main document;
+    /// any others are extra vectors (example tasks in multi-vector mode).
+    pub fn documents(&self, cap: &CapabilityRecord) -> Vec<String> {
+        let mut main: Vec<String> = Vec::new();
+        let mut extra: Vec<String> = Vec::new();
+
+        for field in &self.fields {
+            match field {
+                DocumentField::Summary => main.push(cap.summary.clone()),
+                DocumentField::Fields => {
+                    let inputs = schema_fields(cap.input_schema.as_ref());
+                    if !inputs.is_empty() {
+                        main.push(format!("Inputs: {}", inputs.join(", ")));
+                    }
+                    let outputs = schema_fields(cap.output_schema.as_ref());
+                    if !outputs.is_empty() {
+                        main.push(format!("Outputs: {}", outputs.join(", ")));
+                    }
+                }
+                DocumentField::Examples => {
+                    if self.multi_vector {
+                        extra.extend(cap.example_tasks.iter().cloned());
+                    } else if !cap.example_tasks.is_empty() {
+                        main.push(format!("Examples: {}", cap.example_tasks.join("; ")));
+                    }
+                }
+                DocumentField::Tags => {
+                    if !cap.tags.is_empty() {
+                        main.push(format!("Tags: {}", cap.tags.join(", ")));
+                    }
+                }
+            }
+        }
+
+        // Never embed an empty document (e.g. only "examples" and none recorded).
+        if main.is_empty() {
+            main.push(cap.summary.clone());
+        }
+
+        let mut docs = vec![main.join("\n")];
+        docs.extend(extra);
+        docs
+    }
+}
+
+/// Top-level property names of a JSON Schema, with underscores as spaces.
+fn schema_fields(schema: Option<&serde_json::Value>) -> Vec<String> {
+    schema
+        .and_then(|s| s.get("properties"))
+        .and_then(|p| p.as_object())
+        .map(|props| props.keys().map(|k| k.replace('_', " ")).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn salary() -> CapabilityRecord {
+        CapabilityRecord {
+            input_schema: Some(json!({"properties": {"employee_id": {"type": "string"}}})),
+            output_schema: Some(json!({"properties": {"base_salary": {}, "currency": {}}})),
+            tags: vec!["hr".to_string(), "payroll".to_string()],
+            example_tasks: vec!["How much does EMP001 earn?".to_string()],
+            ..CapabilityRecord::new("get_salary_details", "Returns employee salary details.")
+        }
+    }
+
+    #[test]
+    fn test_default_document_combines_fields() {
+        let docs = IndexDocumentOptions::default().documents(&salary());
+
+        assert_eq!(
+            docs,
+            vec!["Returns employee salary details.\n\
+                 Inputs: employee id\n\
+                 Outputs: base salary, currency\n\
+                 Examples: How much does EMP001 earn?\n\
+                 Tags: hr, payroll"
+                .to_string()]
+        );
+        assert_eq!(
+            IndexDocumentOptions::summary_only().documents(&salary()),
+            vec!["Returns employee salary details.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_multi_vector_splits_out_examples() {
+        let opts = IndexDocumentOptions {
+            multi_vector: true,
+            ..Default::default()
+        };
+        let docs = opts.documents(&salary());
+
+        assert_eq!(docs.len(), 2);
+        assert!(!docs[0].contains("EMP001"));
+        assert_eq!(docs[1], "How much does EMP001 earn?");
+    }
+}
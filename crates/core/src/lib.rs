@@ -2,6 +2,22 @@ pub mod ai_client;
 pub mod capability_index;
 pub mod capability_registry;
 pub mod capability_runner;
+pub mod conversation;
 pub mod embedding;
 pub mod foundry_client;
+pub mod index_document;
+pub mod lifecycle;
+pub mod local_embedder;
+pub mod ollama_embedder;
+pub mod openai_client;
+pub mod openai_embedder;
+pub mod registry_backend;
+pub mod registry_health;
+pub mod retry;
+pub mod run_stats;
+pub mod scripted_client;
+pub mod sqlite_registry;
+pub mod streaming;
+pub mod tool_loop;
 pub mod types;
+pub mod usage;
@@ -0,0 +1,179 @@
+// crates/core/src/lifecycle.rs
+
+use anyhow::Result;
+
+use crate::registry_backend::{RegistryBackend, RegistryChange, RegistryEvent};
+use crate::types::CapabilityStatus;
+
+/// Whether a capability may move from one status to another.
+///
+/// - active -> legacy (superseded) or deprecated (broken)
+/// - legacy -> deprecated, or back to active
+/// - deprecated -> active (revived after revalidation)
+/// - active, legacy or deprecated -> retired, which is terminal
+pub fn is_allowed(from: &CapabilityStatus, to: &CapabilityStatus) -> bool {
+    use CapabilityStatus::*;
+
+    matches!(
+        (from, to),
+        (Active, Legacy)
+            | (Active, Deprecated)
+            | (Legacy, Deprecated)
+            | (Legacy, Active)
+            | (Deprecated, Active)
+            | (Active | Legacy | Deprecated, Retired)
+    )
+}
+
+/// Status changes that go through the allowed transitions and are recorded,
+/// with who made them and why, in the registry's history.
+pub struct Lifecycle<'a> {
+    registry: &'a dyn RegistryBackend,
+}
+
+impl<'a> Lifecycle<'a> {
+    pub fn new(registry: &'a dyn RegistryBackend) -> Self {
+        Self { registry }
+    }
+
+    /// Move a capability to `to`, failing if the transition isn't allowed.
+    /// The check is made by the backend as part of the write, so a
+    /// concurrent change can't slip in between.
+    ///
+    /// Returns the status it moved from.
+    pub fn transition(
+        &self,
+        id: &str,
+        to: CapabilityStatus,
+        replaced_by: Option<&str>,
+        actor: &str,
+        reason: &str,
+    ) -> Result<CapabilityStatus> {
+        let target = to.clone();
+        self.registry.set_status(
+            id,
+            to,
+            &|from| is_allowed(from, &target),
+            replaced_by,
+            actor,
+            Some(reason),
+        )
+    }
+
+    /// Mark a capability as replaced by a newer one.
+    pub fn supersede(&self, id: &str, replaced_by: &str, actor: &str, reason: &str) -> Result<()> {
+        if self.registry.get(replaced_by)?.is_none() {
+            anyhow::bail!("replacement capability '{}' not found", replaced_by);
+        }
+        self.transition(
+            id,
+            CapabilityStatus::Legacy,
+            Some(replaced_by),
+            actor,
+            reason,
+        )
+        .map(|_| ())
+    }
+
+    /// Mark a capability as broken.
+    pub fn deprecate(&self, id: &str, actor: &str, reason: &str) -> Result<()> {
+        self.transition(id, CapabilityStatus::Deprecated, None, actor, reason)
+            .map(|_| ())
+    }
+
+    /// Bring a legacy or deprecated capability back into use, e.g. after it
+    /// has been revalidated. Clears `replaced_by`.
+    pub fn revive(&self, id: &str, actor: &str, reason: &str) -> Result<()> {
+        self.transition(id, CapabilityStatus::Active, None, actor, reason)
+            .map(|_| ())
+    }
+
+    /// Withdraw a capability for good.
+    pub fn retire(&self, id: &str, actor: &str, reason: &str) -> Result<()> {
+        self.transition(id, CapabilityStatus::Retired, None, actor, reason)
+            .map(|_| ())
+    }
+
+    /// Status changes for a capability, oldest first.
+    pub fn audit_log(&self, id: &str) -> Result<Vec<RegistryEvent>> {
+        Ok(self
+            .registry
+            .history(id)?
+            .into_iter()
+            .filter(|e| matches!(e.change, RegistryChange::StatusChanged { .. }))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite_registry::SqliteRegistry;
+    use crate::types::CapabilityRecord;
+
+    #[test]
+    fn test_transition_table() {
+        use CapabilityStatus::*;
+
+        assert!(is_allowed(&Active, &Legacy));
+        assert!(is_allowed(&Deprecated, &Active));
+        assert!(is_allowed(&Legacy, &Retired));
+        assert!(!is_allowed(&Active, &Active));
+        assert!(!is_allowed(&Deprecated, &Legacy));
+        assert!(!is_allowed(&Retired, &Active));
+    }
+
+    #[test]
+    fn test_lifecycle_audits_and_enforces_transitions() {
+        let db = SqliteRegistry::open_in_memory().unwrap();
+        db.import(&[
+            CapabilityRecord::new("get_salary_details", "get_salary_details"),
+            CapabilityRecord::new("update_employee_salary", "update_employee_salary"),
+        ])
+        .unwrap();
+        let lifecycle = Lifecycle::new(&db);
+
+        lifecycle
+            .supersede(
+                "get_salary_details",
+                "update_employee_salary",
+                "mutation_agent",
+                "adds updates",
+            )
+            .unwrap();
+        assert!(lifecycle
+            .supersede("get_salary_details", "missing", "cli", "typo")
+            .is_err());
+
+        lifecycle
+            .revive("get_salary_details", "cli", "replacement dropped reads")
+            .unwrap();
+        let revived = db.get("get_salary_details").unwrap().unwrap();
+        assert_eq!(revived.status, CapabilityStatus::Active);
+        assert_eq!(revived.replaced_by, None);
+
+        lifecycle
+            .deprecate("get_salary_details", "cli", "returns stale salaries")
+            .unwrap();
+        let deprecated = db.get("get_salary_details").unwrap().unwrap();
+        assert_eq!(
+            deprecated.deprecated_reason.as_deref(),
+            Some("returns stale salaries")
+        );
+
+        lifecycle
+            .retire("get_salary_details", "cli", "no longer needed")
+            .unwrap();
+        let refused = lifecycle
+            .revive("get_salary_details", "cli", "try again")
+            .unwrap_err();
+        assert!(refused.to_string().contains("cannot go from Retired"));
+        let retired = db.get("get_salary_details").unwrap().unwrap();
+        assert_eq!(retired.status, CapabilityStatus::Retired);
+
+        let log = lifecycle.audit_log("get_salary_details").unwrap();
+        let actors: Vec<&str> = log.iter().filter_map(|e| e.actor.as_deref()).collect();
+        assert_eq!(actors, vec!["mutation_agent", "cli", "cli", "cli"]);
+        assert_eq!(log[3].reason.as_deref(), Some("no longer needed"));
+    }
+}
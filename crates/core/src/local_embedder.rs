@@ -0,0 +1,189 @@
+// crates/core/src/local_embedder.rs
+
+use anyhow::{Context, Result};
+
+use crate::embedding::Embedder;
+
+/// Default dimension of locally hashed embeddings.
+const DEFAULT_DIM: usize = 512;
+
+/// Weight of a whole-word feature.
+const WORD_WEIGHT: f32 = 1.0;
+/// Weight of an adjacent word pair feature.
+const BIGRAM_WEIGHT: f32 = 0.5;
+/// Total weight spread across a word's character trigrams.
+const TRIGRAM_WEIGHT: f32 = 0.6;
+
+/// Words that carry no meaning for retrieval.
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "into", "is", "it",
+    "its", "of", "on", "or", "that", "the", "this", "to", "with",
+];
+
+/// Offline embedder using feature hashing over words, word pairs and
+/// character trigrams.
+///
+/// Needs no model files, network access or credentials, so retrieval works
+/// fully offline (and in tests). Character trigrams make "salary" match
+/// "salaries"; word pairs reward matching phrases. Vectors are L2-normalised.
+///
+/// Optional environment variables:
+///
+/// - LOCAL_EMBED_DIM
+///   default: 512
+pub struct LocalEmbedder {
+    dim: usize,
+}
+
+impl LocalEmbedder {
+    /// Construct with an explicit vector dimension.
+    pub fn new(dim: usize) -> Self {
+        Self { dim: dim.max(1) }
+    }
+
+    /// Construct from environment variables.
+    pub fn from_env() -> Result<Self> {
+        let dim = match std::env::var("LOCAL_EMBED_DIM") {
+            Ok(v) => v
+                .parse::<usize>()
+                .with_context(|| format!("LOCAL_EMBED_DIM is not a number: {}", v))?,
+            Err(_) => DEFAULT_DIM,
+        };
+
+        if dim == 0 {
+            anyhow::bail!("LOCAL_EMBED_DIM must be greater than zero");
+        }
+
+        Ok(Self::new(dim))
+    }
+
+    /// Dimension of the produced vectors.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    fn add_feature(&self, vector: &mut [f32], feature: &str, weight: f32) {
+        let hash = fnv1a(feature.as_bytes());
+        let slot = (hash % self.dim as u64) as usize;
+        // Use an independent bit for the sign so collisions tend to cancel out.
+        let sign = if (hash >> 63) & 1 == 0 { 1.0 } else { -1.0 };
+        vector[slot] += sign * weight;
+    }
+}
+
+impl Default for LocalEmbedder {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIM)
+    }
+}
+
+impl Embedder for LocalEmbedder {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let mut vector = vec![0.0f32; self.dim];
+        let words = tokenize(text);
+
+        for word in &words {
+            self.add_feature(&mut vector, &format!("w:{}", word), WORD_WEIGHT);
+
+            let padded: Vec<char> = format!("#{}#", word).chars().collect();
+            let trigrams = padded.len().saturating_sub(2);
+            for i in 0..trigrams {
+                let gram: String = padded[i..i + 3].iter().collect();
+                self.add_feature(
+                    &mut vector,
+                    &format!("c:{}", gram),
+                    TRIGRAM_WEIGHT / trigrams as f32,
+                );
+            }
+        }
+
+        for pair in words.windows(2) {
+            self.add_feature(
+                &mut vector,
+                &format!("b:{} {}", pair[0], pair[1]),
+                BIGRAM_WEIGHT,
+            );
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            for x in vector.iter_mut() {
+                *x /= norm;
+            }
+        }
+
+        Ok(vector)
+    }
+
+    fn model_id(&self) -> Option<String> {
+        Some(format!("local:{}", self.dim))
+    }
+}
+
+/// Lowercase, split on anything that isn't alphanumeric and drop stopwords.
+fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty() && !STOPWORDS.contains(w))
+        .map(|w| w.to_string())
+        .collect()
+}
+
+/// 64-bit FNV-1a: stable across runs and platforms, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_embeddings_are_normalised_and_deterministic() {
+        let embedder = LocalEmbedder::new(256);
+        let a = embedder
+            .embed("Get salary details for employee E001")
+            .unwrap();
+        let b = embedder
+            .embed("Get salary details for employee E001")
+            .unwrap();
+
+        assert_eq!(a.len(), 256);
+        assert_eq!(a, b);
+        assert!((dot(&a, &a) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_related_text_scores_higher_than_unrelated() {
+        let embedder = LocalEmbedder::default();
+        let query = embedder
+            .embed("What is the salary of employee E002?")
+            .unwrap();
+        let salary = embedder
+            .embed(
+                "Returns employee salary and compensation details including base salary and bonus.",
+            )
+            .unwrap();
+        let car = embedder
+            .embed("Returns company car details such as make, model and registration.")
+            .unwrap();
+
+        assert!(dot(&query, &salary) > dot(&query, &car));
+    }
+
+    #[test]
+    fn test_empty_text_yields_zero_vector() {
+        let embedder = LocalEmbedder::new(32);
+        let v = embedder.embed("  the of ").unwrap();
+        assert!(v.iter().all(|x| *x == 0.0));
+    }
+}
@@ -0,0 +1,126 @@
+// crates/core/src/ollama_embedder.rs
+
+use anyhow::{Context, Result};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::embedding::Embedder;
+use crate::retry::check_status;
+
+/// Embedding client for a local Ollama instance (`/api/embeddings`).
+///
+/// Expects the following environment variables:
+///
+/// - OLLAMA_EMBED_MODEL
+///   e.g. "nomic-embed-text"
+///
+/// - OLLAMA_BASE_URL (optional)
+///   default: "http://localhost:11434"
+pub struct OllamaEmbedder {
+    client: Client,
+    url: String,
+    model: String,
+}
+
+impl OllamaEmbedder {
+    /// Construct with explicit parameters.
+    pub fn new(base_url: &str, model: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: format!("{}/api/embeddings", base_url.trim_end_matches('/')),
+            model: model.to_string(),
+        }
+    }
+
+    /// Construct from environment variables.
+    pub fn from_env() -> Result<Self> {
+        let base_url = std::env::var("OLLAMA_BASE_URL")
+            .unwrap_or_else(|_| "http://localhost:11434".to_string());
+
+        let model = std::env::var("OLLAMA_EMBED_MODEL").context("OLLAMA_EMBED_MODEL not set")?;
+
+        Ok(Self::new(&base_url, &model))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaEmbeddingRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+impl OllamaEmbeddingResponse {
+    fn into_embedding(self) -> Result<Vec<f32>> {
+        if self.embedding.is_empty() {
+            anyhow::bail!("Ollama embeddings response contained an empty vector");
+        }
+        Ok(self.embedding)
+    }
+}
+
+impl Embedder for OllamaEmbedder {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let body = OllamaEmbeddingRequest {
+            model: &self.model,
+            prompt: text,
+        };
+
+        let resp = self
+            .client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .context("failed to send Ollama embedding request")?;
+
+        let resp = check_status(resp, "Ollama embeddings")?;
+
+        let parsed: OllamaEmbeddingResponse = resp
+            .json()
+            .context("failed to parse Ollama embeddings response JSON")?;
+
+        parsed.into_embedding()
+    }
+
+    fn model_id(&self) -> Option<String> {
+        Some(format!("ollama:{}", self.model))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_request_body_and_response_parsing() {
+        let embedder = OllamaEmbedder::new("http://localhost:11434/", "nomic-embed-text");
+        assert_eq!(embedder.url, "http://localhost:11434/api/embeddings");
+
+        let body = serde_json::to_value(OllamaEmbeddingRequest {
+            model: &embedder.model,
+            prompt: "employee salary",
+        })
+        .unwrap();
+        assert_eq!(
+            body,
+            json!({"model": "nomic-embed-text", "prompt": "employee salary"})
+        );
+
+        let parsed: OllamaEmbeddingResponse =
+            serde_json::from_str(r#"{"embedding": [0.25, -0.5]}"#).unwrap();
+        assert_eq!(parsed.into_embedding().unwrap(), vec![0.25, -0.5]);
+
+        let empty: OllamaEmbeddingResponse = serde_json::from_str(r#"{"embedding": []}"#).unwrap();
+        assert!(empty.into_embedding().is_err());
+        assert!(
+            serde_json::from_str::<OllamaEmbeddingResponse>(r#"{"error": "model not found"}"#)
+                .is_err()
+        );
+    }
+}
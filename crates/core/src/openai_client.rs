@@ -0,0 +1,241 @@
+// crates/core/src/openai_client.rs
+
+use anyhow::{Context, Result};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::ai_client::{AiClient, ChatRequest, ChatResponse};
+use crate::retry::check_status;
+use crate::streaming::{emit_response, read_stream, OnDelta};
+
+/// Chat client for any OpenAI-compatible `/v1/chat/completions` API
+/// (OpenAI, vLLM, llama.cpp server, Ollama's `/v1` endpoint, ...).
+///
+/// Expects the following environment variables:
+///
+/// - OPENAI_CHAT_MODEL
+///   e.g. "gpt-4o", "qwen2.5-coder:14b"
+///
+/// - OPENAI_BASE_URL (optional)
+///   default: "https://api.openai.com/v1"
+///
+/// - OPENAI_API_KEY (optional)
+///   sent as a bearer token; local servers usually don't need one
+///
+/// - OPENAI_EXTRA_HEADERS (optional)
+///   JSON object of extra request headers, e.g. '{"OpenAI-Organization": "org-..."}'
+pub struct OpenAiClient {
+    client: Client,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    headers: Vec<(String, String)>,
+    stream: bool,
+}
+
+impl OpenAiClient {
+    /// Construct with explicit parameters.
+    ///
+    /// `base_url` is the API root including the version segment,
+    /// e.g. "http://localhost:11434/v1".
+    pub fn new(base_url: &str, model: &str, api_key: Option<&str>) -> Self {
+        Self {
+            client: Client::new(),
+            url: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            model: model.to_string(),
+            api_key: api_key.map(|k| k.to_string()),
+            headers: Vec::new(),
+            stream: true,
+        }
+    }
+
+    /// Whether `chat_stream` asks for a streamed response (default: true).
+    pub fn with_streaming(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    /// Send an extra header with every request.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Construct from environment variables using OPENAI_CHAT_MODEL.
+    pub fn from_env() -> Result<Self> {
+        Self::from_env_with_prefix("OPENAI")
+    }
+
+    /// Construct from environment variables named `<prefix>_...`.
+    pub fn from_env_with_prefix(prefix: &str) -> Result<Self> {
+        Self::from_vars_with_prefix(prefix, |name| std::env::var(name).ok())
+    }
+
+    /// Construct from variables named `<prefix>_...`, looked up with `vars`.
+    ///
+    /// Each variable falls back to its `OPENAI_...` counterpart, so e.g.
+    /// `OPENAI_MUTATION` only needs the settings that differ. The model is
+    /// read from `<prefix>_MODEL` (e.g. OPENAI_MUTATION_MODEL), falling back
+    /// to OPENAI_CHAT_MODEL.
+    ///
+    /// Credentials (API_KEY and EXTRA_HEADERS) don't fall back when
+    /// `<prefix>_BASE_URL` is set, so the OpenAI key is never sent to
+    /// another server.
+    pub fn from_vars_with_prefix(
+        prefix: &str,
+        vars: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let own = |name: &str| vars(&format!("{}_{}", prefix, name)).filter(|v| !v.is_empty());
+        let var = |name: &str| {
+            own(name).or_else(|| vars(&format!("OPENAI_{}", name)).filter(|v| !v.is_empty()))
+        };
+        let credential = |name: &str| match own("BASE_URL") {
+            Some(_) => own(name),
+            None => var(name),
+        };
+
+        let base_url = var("BASE_URL").unwrap_or_else(|| "https://api.openai.com/v1".to_string());
+
+        let model = vars(&format!("{}_MODEL", prefix))
+            .filter(|_| prefix != "OPENAI")
+            .or_else(|| vars("OPENAI_CHAT_MODEL"))
+            .context("OPENAI_CHAT_MODEL not set")?;
+
+        let mut client = Self::new(&base_url, &model, credential("API_KEY").as_deref());
+
+        if let Some(raw) = credential("EXTRA_HEADERS") {
+            let headers: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&raw)
+                .with_context(|| {
+                    format!("{}_EXTRA_HEADERS is not a JSON object: {}", prefix, raw)
+                })?;
+            for (name, value) in headers {
+                let value = value
+                    .as_str()
+                    .with_context(|| format!("header '{}' must be a string", name))?;
+                client = client.with_header(&name, value);
+            }
+        }
+
+        Ok(client)
+    }
+}
+
+/// Request body: the shared request plus the model name.
+#[derive(Serialize)]
+struct OpenAiChatRequest<'a> {
+    model: &'a str,
+    #[serde(flatten)]
+    request: &'a ChatRequest,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    /// Asks for a final chunk with token usage when streaming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<Value>,
+}
+
+impl OpenAiClient {
+    fn post(&self, request: &ChatRequest, stream: bool) -> RequestBuilder {
+        let body = OpenAiChatRequest {
+            model: &self.model,
+            request,
+            stream,
+            stream_options: stream.then(|| json!({ "include_usage": true })),
+        };
+
+        let mut req = self.client.post(&self.url).json(&body);
+        if let Some(key) = &self.api_key {
+            req = req.bearer_auth(key);
+        }
+        for (name, value) in &self.headers {
+            req = req.header(name, value);
+        }
+        req
+    }
+}
+
+impl AiClient for OpenAiClient {
+    fn chat(&self, request: ChatRequest) -> Result<ChatResponse> {
+        let resp = self
+            .post(&request, false)
+            .send()
+            .context("failed to send OpenAI chat request")?;
+
+        let resp = check_status(resp, "OpenAI chat")?;
+
+        let parsed: ChatResponse = resp
+            .json()
+            .context("failed to parse OpenAI chat response JSON")?;
+
+        Ok(parsed)
+    }
+
+    fn chat_stream(&self, request: ChatRequest, on_delta: OnDelta<'_>) -> Result<ChatResponse> {
+        if !self.stream {
+            let response = self.chat(request)?;
+            emit_response(&response, on_delta);
+            return Ok(response);
+        }
+
+        let resp = self
+            .post(&request, true)
+            .send()
+            .context("failed to send OpenAI chat request")?;
+
+        let resp = check_status(resp, "OpenAI chat")?;
+
+        read_stream(resp, "OpenAI chat", on_delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_client::Message;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_prefix_does_not_forward_credentials_to_its_own_server() {
+        let mut vars = HashMap::from([
+            ("OPENAI_CHAT_MODEL", "gpt-4o"),
+            ("OPENAI_API_KEY", "sk-real"),
+            ("OPENAI_MUTATION_MODEL", "qwen"),
+        ]);
+        let client = |vars: &HashMap<&str, &str>| {
+            OpenAiClient::from_vars_with_prefix("OPENAI_MUTATION", |name| {
+                vars.get(name).map(|v| v.to_string())
+            })
+            .unwrap()
+        };
+
+        let shared = client(&vars);
+        assert_eq!(shared.api_key.as_deref(), Some("sk-real"));
+
+        vars.insert("OPENAI_MUTATION_BASE_URL", "http://localhost:8000/v1");
+        let local = client(&vars);
+        assert_eq!(local.model, "qwen");
+        assert_eq!(local.api_key, None);
+
+        vars.insert("OPENAI_MUTATION_API_KEY", "local-key");
+        let local = client(&vars);
+        assert_eq!(local.api_key.as_deref(), Some("local-key"));
+    }
+
+    #[test]
+    fn test_request_body_adds_model_to_shared_request() {
+        let request = ChatRequest::new(vec![Message::user("hi")])
+            .with_tools(vec![json!({"type": "function"})]);
+        let body = serde_json::to_value(OpenAiChatRequest {
+            model: "qwen2.5-coder:14b",
+            request: &request,
+            stream: false,
+            stream_options: None,
+        })
+        .unwrap();
+
+        assert_eq!(body["model"], "qwen2.5-coder:14b");
+        assert_eq!(body["messages"][0]["content"], "hi");
+        assert_eq!(body["tool_choice"], "auto");
+        assert!(body.get("stream").is_none());
+    }
+}
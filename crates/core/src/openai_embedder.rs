@@ -0,0 +1,183 @@
+// crates/core/src/openai_embedder.rs
+
+use anyhow::{Context, Result};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::embedding::{ordered_embeddings, Embedder, EmbeddingData};
+use crate::retry::check_status;
+
+/// Default max inputs per request in `embed_batch`.
+const DEFAULT_BATCH_SIZE: usize = 64;
+
+/// Embedding client for any OpenAI-compatible `/v1/embeddings` API
+/// (OpenAI, vLLM, LM Studio, llama.cpp server, ...).
+///
+/// Expects the following environment variables:
+///
+/// - OPENAI_EMBED_MODEL
+///   e.g. "text-embedding-3-small"
+///
+/// - OPENAI_BASE_URL (optional)
+///   default: "https://api.openai.com/v1"
+///
+/// - OPENAI_API_KEY (optional)
+///   sent as a bearer token; local servers usually don't need one
+///
+/// - OPENAI_EMBED_BATCH_SIZE (optional)
+///   max inputs per request in `embed_batch`, default: 64
+pub struct OpenAiEmbedder {
+    client: Client,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    batch_size: usize,
+}
+
+impl OpenAiEmbedder {
+    /// Construct with explicit parameters.
+    ///
+    /// `base_url` is the API root including the version segment,
+    /// e.g. "http://localhost:8000/v1".
+    pub fn new(base_url: &str, model: &str, api_key: Option<&str>) -> Self {
+        Self {
+            client: Client::new(),
+            url: format!("{}/embeddings", base_url.trim_end_matches('/')),
+            model: model.to_string(),
+            api_key: api_key.map(|k| k.to_string()),
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Set the max number of inputs sent in one request.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Construct from environment variables.
+    pub fn from_env() -> Result<Self> {
+        let base_url = std::env::var("OPENAI_BASE_URL")
+            .unwrap_or_else(|_| "https://api.openai.com/v1".to_string());
+
+        let model = std::env::var("OPENAI_EMBED_MODEL").context("OPENAI_EMBED_MODEL not set")?;
+
+        let api_key = std::env::var("OPENAI_API_KEY")
+            .ok()
+            .filter(|k| !k.is_empty());
+
+        let batch_size = match std::env::var("OPENAI_EMBED_BATCH_SIZE") {
+            Ok(v) => v
+                .parse::<usize>()
+                .with_context(|| format!("OPENAI_EMBED_BATCH_SIZE is not a number: {}", v))?,
+            Err(_) => DEFAULT_BATCH_SIZE,
+        };
+
+        Ok(Self::new(&base_url, &model, api_key.as_deref()).with_batch_size(batch_size))
+    }
+
+    /// Embed one chunk of inputs in a single request.
+    fn embed_chunk(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let body = OpenAiEmbeddingRequest {
+            model: &self.model,
+            input: texts,
+        };
+
+        let mut req = self.client.post(&self.url).json(&body);
+        if let Some(key) = &self.api_key {
+            req = req.bearer_auth(key);
+        }
+
+        let resp = req
+            .send()
+            .context("failed to send OpenAI embedding request")?;
+
+        let resp = check_status(resp, "OpenAI embeddings")?;
+
+        let parsed: OpenAiEmbeddingResponse = resp
+            .json()
+            .context("failed to parse OpenAI embeddings response JSON")?;
+
+        ordered_embeddings(parsed.data, texts.len())
+            .context("OpenAI embeddings response did not match the request")
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct OpenAiEmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [&'a str],
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiEmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+impl Embedder for OpenAiEmbedder {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_chunk(&[text])?
+            .into_iter()
+            .next()
+            .context("OpenAI embeddings response contained no data")
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut out = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(self.batch_size) {
+            out.extend(self.embed_chunk(chunk)?);
+        }
+        Ok(out)
+    }
+
+    fn model_id(&self) -> Option<String> {
+        Some(format!("openai:{}", self.model))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_request_body_and_batch_size() {
+        let embedder =
+            OpenAiEmbedder::new("http://localhost:8000/v1/", "bge-m3", None).with_batch_size(0);
+        assert_eq!(embedder.url, "http://localhost:8000/v1/embeddings");
+        assert_eq!(embedder.batch_size, 1);
+        assert!(embedder.api_key.is_none());
+
+        let body = serde_json::to_value(OpenAiEmbeddingRequest {
+            model: &embedder.model,
+            input: &["salary", "car"],
+        })
+        .unwrap();
+        assert_eq!(body, json!({"model": "bge-m3", "input": ["salary", "car"]}));
+    }
+
+    #[test]
+    fn test_response_is_put_back_in_input_order() {
+        let parsed: OpenAiEmbeddingResponse = serde_json::from_str(
+            r#"{
+                "object": "list",
+                "data": [
+                    {"object": "embedding", "index": 1, "embedding": [0.0, 1.0]},
+                    {"object": "embedding", "index": 0, "embedding": [1.0, 0.0]}
+                ],
+                "model": "bge-m3",
+                "usage": {"prompt_tokens": 2, "total_tokens": 2}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ordered_embeddings(parsed.data, 2).unwrap(),
+            vec![vec![1.0, 0.0], vec![0.0, 1.0]]
+        );
+
+        let short: OpenAiEmbeddingResponse =
+            serde_json::from_str(r#"{"data": [{"embedding": [1.0]}]}"#).unwrap();
+        assert!(ordered_embeddings(short.data, 2).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+// crates/core/src/registry_backend.rs
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::capability_registry::CapabilityRegistry;
+use crate::registry_health::HealthIssue;
+use crate::run_stats::{self, CapabilityRunStats, RUN_STATS_FILE};
+use crate::sqlite_registry::SqliteRegistry;
+use crate::types::{CapabilityRecord, CapabilityStatus};
+
+/// Default SQLite database file name under the capabilities root.
+const DEFAULT_SQLITE_FILE: &str = "registry.db";
+
+/// What happened to a capability in the registry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RegistryChange {
+    /// Metadata was created or replaced.
+    Upserted,
+    /// Lifecycle status changed.
+    StatusChanged {
+        from: CapabilityStatus,
+        to: CapabilityStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        replaced_by: Option<String>,
+    },
+    /// The capability was unregistered (e.g. pruned).
+    Removed,
+}
+
+/// One entry in a capability's history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryEvent {
+    pub capability_id: String,
+    /// RFC 3339 timestamp.
+    pub at: String,
+    #[serde(flatten)]
+    pub change: RegistryChange,
+    /// Who made the change (e.g. "agent", "mutation_agent", "cli").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl RegistryEvent {
+    pub fn now(
+        capability_id: &str,
+        change: RegistryChange,
+        actor: Option<&str>,
+        reason: Option<&str>,
+    ) -> Self {
+        Self {
+            capability_id: capability_id.to_string(),
+            at: chrono::Utc::now().to_rfc3339(),
+            change,
+            actor: actor.map(|a| a.to_string()),
+            reason: reason.map(|r| r.to_string()),
+        }
+    }
+}
+
+/// A main-document vector cached by a registry backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedEmbedding {
+    pub capability_id: String,
+    /// `Embedder::model_id` of the embedder that produced it.
+    pub model: String,
+    /// The text that was embedded.
+    pub document: String,
+    pub vector: Vec<f32>,
+}
+
+/// Storage for capability metadata, run stats and cached embeddings.
+///
+/// Every metadata write is recorded in the capability's history. Binaries
+/// and source code stay under `crates/<id>/` whichever backend holds
+/// metadata.
+pub trait RegistryBackend {
+    /// All registered capabilities.
+    fn load(&self) -> Result<Vec<CapabilityRecord>>;
+
+    /// Like `load`, but reports unreadable or inconsistent entries as
+    /// issues instead of failing. See `registry_health::load_validated`.
+    fn load_checked(&self) -> Result<(Vec<CapabilityRecord>, Vec<HealthIssue>)> {
+        Ok((self.load()?, Vec::new()))
+    }
+
+    /// A single capability, if registered.
+    fn get(&self, id: &str) -> Result<Option<CapabilityRecord>>;
+
+    /// Create or replace a capability's metadata.
+    fn upsert(&self, record: &CapabilityRecord) -> Result<()>;
+
+    /// Change a capability's lifecycle status, optionally naming its
+    /// replacement, and return the status it moved from. Deprecating records
+    /// `reason` as `deprecated_reason`; moving back to active clears it and
+    /// `replaced_by`.
+    ///
+    /// `allowed` is called with the current status as part of the same write,
+    /// and the change is refused if it returns false. Callers normally go
+    /// through `lifecycle::Lifecycle`, which passes the allowed transitions.
+    fn set_status(
+        &self,
+        id: &str,
+        status: CapabilityStatus,
+        allowed: &dyn Fn(&CapabilityStatus) -> bool,
+        replaced_by: Option<&str>,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> Result<CapabilityStatus>;
+
+    /// Unregister a capability. Its history is kept and ends with a
+    /// `Removed` event; crate sources and binaries are left to the caller.
+    fn remove(&self, id: &str, actor: &str, reason: Option<&str>) -> Result<()>;
+
+    /// Changes to a capability, oldest first.
+    fn history(&self, id: &str) -> Result<Vec<RegistryEvent>>;
+
+    /// Run history of every capability that has run.
+    fn run_stats(&self) -> Result<HashMap<String, CapabilityRunStats>>;
+
+    /// Count one run of a capability, returning its updated stats.
+    fn record_run(&self, id: &str, success: bool, latency_ms: u64) -> Result<CapabilityRunStats>;
+
+    /// The cached main-document vector for a capability, if one was computed
+    /// by `model` from exactly `document`. Backends without a cache return
+    /// `None`.
+    fn cached_embedding(
+        &self,
+        _id: &str,
+        _model: &str,
+        _document: &str,
+    ) -> Result<Option<Vec<f32>>> {
+        Ok(None)
+    }
+
+    /// Cache freshly computed vectors, replacing any older ones for the same
+    /// capabilities.
+    fn cache_embeddings(&self, _embeddings: &[CachedEmbedding]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Build the registry backend selected by environment variables.
+///
+/// - REGISTRY_BACKEND: "fs" (default) or "sqlite"
+/// - REGISTRY_SQLITE_PATH (default: "<capabilities_root>/registry.db")
+///
+/// The SQLite registry imports `meta.json` files and `run_stats.json`
+/// entries under `capabilities_root` for ids it doesn't have yet.
+/// Error for a status change that `set_status`'s `allowed` check refused.
+pub(crate) fn transition_refused(
+    id: &str,
+    from: &CapabilityStatus,
+    to: &CapabilityStatus,
+) -> anyhow::Error {
+    anyhow::anyhow!("capability '{}' cannot go from {:?} to {:?}", id, from, to)
+}
+
+pub fn registry_from_env(capabilities_root: &str) -> Result<Box<dyn RegistryBackend>> {
+    let backend = std::env::var("REGISTRY_BACKEND").unwrap_or_else(|_| "fs".to_string());
+
+    match backend.to_lowercase().as_str() {
+        "fs" | "filesystem" => Ok(Box::new(CapabilityRegistry::new(capabilities_root))),
+        "sqlite" => {
+            let path = std::env::var("REGISTRY_SQLITE_PATH").unwrap_or_else(|_| {
+                Path::new(capabilities_root)
+                    .join(DEFAULT_SQLITE_FILE)
+                    .to_string_lossy()
+                    .into_owned()
+            });
+            let db = SqliteRegistry::open(&path)?;
+
+            // The database is authoritative; meta.json files are only read
+            // for ids it doesn't have yet (everything on first use, crates
+            // added on disk later). Bad files are reported by the fs backend.
+            let (on_disk, _) = CapabilityRegistry::new(capabilities_root).load_checked()?;
+            let imported = db.import_new(&on_disk)?;
+            if imported > 0 {
+                eprintln!(
+                    "[registry] Imported {} capabilities from {} into {}",
+                    imported, capabilities_root, path
+                );
+            }
+            let stats = run_stats::load_file(&Path::new(capabilities_root).join(RUN_STATS_FILE))?;
+            let imported = db.import_run_stats(&stats)?;
+            if imported > 0 {
+                eprintln!(
+                    "[registry] Imported run stats for {} capabilities into {}",
+                    imported, path
+                );
+            }
+
+            Ok(Box::new(db))
+        }
+        other => anyhow::bail!(
+            "unknown REGISTRY_BACKEND '{}' (expected fs or sqlite)",
+            other
+        ),
+    }
+}
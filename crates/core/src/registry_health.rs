@@ -0,0 +1,309 @@
+// crates/core/src/registry_health.rs
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::registry_backend::RegistryBackend;
+use crate::types::CapabilityRecord;
+
+/// How bad an issue is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The capability loads but something is off.
+    Warning,
+    /// The capability can't be loaded safely.
+    Error,
+}
+
+/// A problem found while loading or validating the registry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    /// A capability crate directory has no meta.json.
+    MissingMeta,
+    /// meta.json (or a stored record) couldn't be parsed.
+    MalformedMeta(String),
+    /// meta.json `id` differs from its directory name.
+    IdMismatch { dir: String },
+    /// `status` isn't one of active, legacy, deprecated or retired.
+    UnknownStatus(String),
+    /// No `binary` is configured.
+    NoBinary,
+    /// The configured binary doesn't exist (e.g. not built yet).
+    MissingBinary(String),
+    /// `replaced_by` names a capability that isn't registered.
+    DanglingReplacedBy(String),
+    /// Cached embedding dimension differs from the rest of the registry.
+    EmbeddingDimMismatch { expected: usize, found: usize },
+}
+
+impl IssueKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            IssueKind::MissingMeta
+            | IssueKind::NoBinary
+            | IssueKind::MissingBinary(_)
+            | IssueKind::DanglingReplacedBy(_) => Severity::Warning,
+            IssueKind::MalformedMeta(_)
+            | IssueKind::IdMismatch { .. }
+            | IssueKind::UnknownStatus(_)
+            | IssueKind::EmbeddingDimMismatch { .. } => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueKind::MissingMeta => write!(f, "no meta.json, skipped"),
+            IssueKind::MalformedMeta(err) => write!(f, "malformed metadata: {}", err),
+            IssueKind::IdMismatch { dir } => {
+                write!(f, "id does not match its directory '{}'", dir)
+            }
+            IssueKind::UnknownStatus(status) => write!(f, "unknown status '{}'", status),
+            IssueKind::NoBinary => write!(f, "no binary configured"),
+            IssueKind::MissingBinary(path) => write!(f, "binary not found at {}", path),
+            IssueKind::DanglingReplacedBy(id) => {
+                write!(f, "replaced_by '{}' is not a registered capability", id)
+            }
+            IssueKind::EmbeddingDimMismatch { expected, found } => write!(
+                f,
+                "cached embedding has dimension {} but the registry uses {}",
+                found, expected
+            ),
+        }
+    }
+}
+
+/// An issue attached to the capability (or directory) it concerns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthIssue {
+    /// Capability id, or the directory name when the id couldn't be read.
+    pub capability_id: String,
+    pub kind: IssueKind,
+}
+
+impl HealthIssue {
+    pub fn new(capability_id: &str, kind: IssueKind) -> Self {
+        Self {
+            capability_id: capability_id.to_string(),
+            kind,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+/// What to do with capabilities that have errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadMode {
+    /// Leave them out and keep going.
+    #[default]
+    Quarantine,
+    /// Refuse to start.
+    Strict,
+}
+
+impl LoadMode {
+    /// Read REGISTRY_LOAD_MODE ("quarantine" (default) or "strict").
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("REGISTRY_LOAD_MODE") {
+            Err(_) => Ok(Self::default()),
+            Ok(v) => match v.to_lowercase().as_str() {
+                "quarantine" => Ok(Self::Quarantine),
+                "strict" => Ok(Self::Strict),
+                other => anyhow::bail!(
+                    "unknown REGISTRY_LOAD_MODE '{}' (expected quarantine or strict)",
+                    other
+                ),
+            },
+        }
+    }
+}
+
+/// Result of validating the registry.
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport {
+    /// Number of records read, before quarantine.
+    pub loaded: usize,
+    pub issues: Vec<HealthIssue>,
+}
+
+impl HealthReport {
+    pub fn errors(&self) -> impl Iterator<Item = &HealthIssue> {
+        self.issues
+            .iter()
+            .filter(|i| i.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &HealthIssue> {
+        self.issues
+            .iter()
+            .filter(|i| i.severity() == Severity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Ids of capabilities with at least one error.
+    pub fn quarantined_ids(&self) -> HashSet<&str> {
+        self.errors().map(|i| i.capability_id.as_str()).collect()
+    }
+
+    /// One line per issue, errors first, then by capability id.
+    pub fn lines(&self) -> Vec<String> {
+        let mut issues: Vec<&HealthIssue> = self.issues.iter().collect();
+        issues.sort_by_key(|i| (std::cmp::Reverse(i.severity()), i.capability_id.as_str()));
+        issues
+            .into_iter()
+            .map(|i| {
+                let level = match i.severity() {
+                    Severity::Error => "ERROR",
+                    Severity::Warning => "WARN ",
+                };
+                format!("{} {}: {}", level, i.capability_id, i.kind)
+            })
+            .collect()
+    }
+}
+
+/// Load capabilities from a registry and validate them.
+///
+/// `capabilities_root` is where `crates/<id>/<binary>` is resolved. In
+/// quarantine mode capabilities with errors are left out of the result; in
+/// strict mode any error fails the load.
+pub fn load_validated(
+    registry: &dyn RegistryBackend,
+    capabilities_root: &str,
+    mode: LoadMode,
+) -> Result<(Vec<CapabilityRecord>, HealthReport)> {
+    let (records, mut issues) = registry.load_checked()?;
+    issues.extend(validate_records(&records, capabilities_root));
+
+    let report = HealthReport {
+        loaded: records.len(),
+        issues,
+    };
+
+    if mode == LoadMode::Strict && report.has_errors() {
+        anyhow::bail!(
+            "capability registry has errors (REGISTRY_LOAD_MODE=strict):\n{}",
+            report.lines().join("\n")
+        );
+    }
+
+    let quarantined = report.quarantined_ids();
+    let records = records
+        .into_iter()
+        .filter(|r| !quarantined.contains(r.id.as_str()))
+        .collect();
+
+    Ok((records, report))
+}
+
+/// Checks that apply to records from any backend.
+pub fn validate_records(records: &[CapabilityRecord], capabilities_root: &str) -> Vec<HealthIssue> {
+    let mut issues = Vec::new();
+    let ids: HashSet<&str> = records.iter().map(|r| r.id.as_str()).collect();
+    let crates_dir = Path::new(capabilities_root).join("crates");
+
+    // The most common cached dimension is taken as the registry's.
+    let mut dims: HashMap<usize, usize> = HashMap::new();
+    for emb in records.iter().filter_map(|r| r.embedding.as_ref()) {
+        *dims.entry(emb.len()).or_default() += 1;
+    }
+    let expected_dim = dims
+        .into_iter()
+        .max_by_key(|(dim, count)| (*count, *dim))
+        .map(|(dim, _)| dim);
+
+    for record in records {
+        match &record.binary {
+            None => issues.push(HealthIssue::new(&record.id, IssueKind::NoBinary)),
+            Some(binary) => {
+                let path = crates_dir.join(&record.id).join(binary);
+                if !path.exists() {
+                    issues.push(HealthIssue::new(
+                        &record.id,
+                        IssueKind::MissingBinary(path.display().to_string()),
+                    ));
+                }
+            }
+        }
+
+        if let Some(replacement) = &record.replaced_by {
+            if !ids.contains(replacement.as_str()) {
+                issues.push(HealthIssue::new(
+                    &record.id,
+                    IssueKind::DanglingReplacedBy(replacement.clone()),
+                ));
+            }
+        }
+
+        if let (Some(emb), Some(expected)) = (&record.embedding, expected_dim) {
+            if emb.len() != expected {
+                issues.push(HealthIssue::new(
+                    &record.id,
+                    IssueKind::EmbeddingDimMismatch {
+                        expected,
+                        found: emb.len(),
+                    },
+                ));
+            }
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_records_flags_dangling_and_dimension() {
+        let a = CapabilityRecord {
+            embedding: Some(vec![0.0; 4]),
+            replaced_by: Some("missing".to_string()),
+            ..CapabilityRecord::new("a", "a")
+        };
+        let b = CapabilityRecord {
+            embedding: Some(vec![0.0; 4]),
+            ..CapabilityRecord::new("b", "b")
+        };
+        let c = CapabilityRecord {
+            embedding: Some(vec![0.0; 8]),
+            ..CapabilityRecord::new("c", "c")
+        };
+
+        let issues = validate_records(&[a, b, c], "/nonexistent");
+
+        assert!(issues.contains(&HealthIssue::new(
+            "a",
+            IssueKind::DanglingReplacedBy("missing".to_string())
+        )));
+        assert!(issues.contains(&HealthIssue::new(
+            "c",
+            IssueKind::EmbeddingDimMismatch {
+                expected: 4,
+                found: 8
+            }
+        )));
+        assert_eq!(
+            issues
+                .iter()
+                .filter(|i| i.kind == IssueKind::NoBinary)
+                .count(),
+            3
+        );
+
+        let report = HealthReport { loaded: 3, issues };
+        assert_eq!(report.quarantined_ids(), HashSet::from(["c"]));
+        assert!(report.lines()[0].starts_with("ERROR c:"));
+    }
+}
@@ -0,0 +1,437 @@
+// crates/core/src/retry.rs
+
+//! Retries for chat and embedding calls.
+//!
+//! Clients report non-success responses as `HttpStatusError`, so callers can
+//! tell a rate limit or server error from a bad request. `Retrying` wraps any
+//! `AiClient` or `Embedder` and retries 429s, 5xxs and network errors with
+//! exponential backoff and jitter, honouring `Retry-After`.
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use reqwest::blocking::Response;
+use reqwest::header::RETRY_AFTER;
+
+use crate::ai_client::{AiClient, ChatRequest, ChatResponse};
+use crate::embedding::Embedder;
+use crate::streaming::{OnDelta, StreamDelta};
+
+/// A request that got a non-success HTTP status.
+#[derive(Debug, Clone)]
+pub struct HttpStatusError {
+    /// What was requested, e.g. "Foundry chat".
+    pub what: String,
+    pub status: u16,
+    /// Delay asked for by the server's `Retry-After` header.
+    pub retry_after: Option<Duration>,
+    pub body: String,
+}
+
+impl HttpStatusError {
+    /// Rate limited or a server-side failure.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.status, 408 | 429) || (500..600).contains(&self.status)
+    }
+}
+
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} request failed: HTTP {} - {}",
+            self.what, self.status, self.body
+        )
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Pass a successful response through, or turn it into an `HttpStatusError`.
+pub fn check_status(resp: Response, what: &str) -> Result<Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+
+    let retry_after = resp
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = resp
+        .text()
+        .unwrap_or_else(|_| "<failed to read error body>".to_string());
+
+    Err(HttpStatusError {
+        what: what.to_string(),
+        status: status.as_u16(),
+        retry_after,
+        body,
+    }
+    .into())
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+/// Whether an error is worth retrying: a retryable status, a timeout or a
+/// failure to connect. Other request errors (bad URL, TLS setup, a body
+/// that can't be built) fail the same way again, so they aren't retried.
+///
+/// A stream that fails after output has been forwarded isn't retried either,
+/// whatever the error; see `Retrying::chat_stream`.
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(http) = cause.downcast_ref::<HttpStatusError>() {
+            return http.is_retryable();
+        }
+        if let Some(req) = cause.downcast_ref::<reqwest::Error>() {
+            return req.is_timeout() || req.is_connect();
+        }
+        false
+    })
+}
+
+/// How hard to retry.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first (1 disables retries).
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up rather than wait past this much time since the first attempt.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            deadline: Some(Duration::from_secs(120)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Read the policy from environment variables.
+    ///
+    /// - RETRY_MAX_ATTEMPTS (default: 4, 1 disables retries)
+    /// - RETRY_INITIAL_BACKOFF_MS (default: 500)
+    /// - RETRY_MAX_BACKOFF_MS (default: 30000)
+    /// - RETRY_DEADLINE_SECS (default: 120, 0 for no deadline)
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Read the policy from the same variables, looked up with `vars`.
+    pub fn from_vars(vars: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut policy = Self::default();
+        let read = |name: &str| -> Result<Option<u64>> {
+            match vars(name) {
+                Some(v) => Ok(Some(
+                    v.parse()
+                        .with_context(|| format!("invalid {} '{}'", name, v))?,
+                )),
+                None => Ok(None),
+            }
+        };
+
+        if let Some(v) = read("RETRY_MAX_ATTEMPTS")? {
+            policy.max_attempts = v.max(1) as u32;
+        }
+        if let Some(v) = read("RETRY_INITIAL_BACKOFF_MS")? {
+            policy.initial_backoff = Duration::from_millis(v);
+        }
+        if let Some(v) = read("RETRY_MAX_BACKOFF_MS")? {
+            policy.max_backoff = Duration::from_millis(v);
+        }
+        if let Some(v) = read("RETRY_DEADLINE_SECS")? {
+            policy.deadline = (v > 0).then(|| Duration::from_secs(v));
+        }
+
+        Ok(policy)
+    }
+
+    /// Backoff before retry number `retry` (0-based): exponential, capped,
+    /// with jitter in the upper half so concurrent clients spread out.
+    fn backoff(&self, retry: u32) -> Duration {
+        let exp = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        exp.mul_f64(0.5 + 0.5 * jitter())
+    }
+
+    /// Run `op`, retrying retryable failures.
+    pub fn run<T>(&self, what: &str, op: impl FnMut() -> Result<T>) -> Result<T> {
+        self.run_while(what, is_retryable, op)
+    }
+
+    /// Run `op`, retrying failures for which `retryable` returns true.
+    fn run_while<T>(
+        &self,
+        what: &str,
+        retryable: impl Fn(&anyhow::Error) -> bool,
+        mut op: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        let started = Instant::now();
+        let mut attempt = 1;
+
+        loop {
+            let err = match op() {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+
+            if attempt >= self.max_attempts || !retryable(&err) {
+                return Err(err);
+            }
+
+            let delay = err
+                .chain()
+                .find_map(|c| c.downcast_ref::<HttpStatusError>())
+                .and_then(|h| h.retry_after)
+                .unwrap_or_else(|| self.backoff(attempt - 1));
+
+            if let Some(deadline) = self.deadline {
+                if started.elapsed() + delay > deadline {
+                    return Err(err.context(format!(
+                        "{} gave up after {} attempts ({:?} deadline)",
+                        what, attempt, deadline
+                    )));
+                }
+            }
+
+            println!(
+                "[RETRY] {} attempt {}/{} failed: {}; retrying in {:.1}s",
+                what,
+                attempt,
+                self.max_attempts,
+                err,
+                delay.as_secs_f32()
+            );
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+/// A number in 0..1 that differs between calls.
+fn jitter() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(Instant::now().elapsed().as_nanos());
+    (hasher.finish() % 10_000) as f64 / 10_000.0
+}
+
+/// Retries calls to the wrapped client or embedder according to a policy.
+pub struct Retrying<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T> Retrying<T> {
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+impl<C: AiClient> AiClient for Retrying<C> {
+    fn chat(&self, request: ChatRequest) -> Result<ChatResponse> {
+        self.policy.run("chat", || self.inner.chat(request.clone()))
+    }
+
+    /// Only failures before the first delta are retried. Once output has
+    /// been forwarded a retry would repeat it, so the error is returned.
+    fn chat_stream(&self, request: ChatRequest, on_delta: OnDelta<'_>) -> Result<ChatResponse> {
+        let streamed = Cell::new(false);
+        let mut forward = |delta: StreamDelta<'_>| {
+            streamed.set(true);
+            on_delta(delta);
+        };
+        self.policy.run_while(
+            "chat",
+            |err| !streamed.get() && is_retryable(err),
+            || self.inner.chat_stream(request.clone(), &mut forward),
+        )
+    }
+}
+
+impl<E: Embedder> Embedder for Retrying<E> {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.policy.run("embed", || self.inner.embed(text))
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.policy
+            .run("embed_batch", || self.inner.embed_batch(texts))
+    }
+
+    fn model_id(&self) -> Option<String> {
+        self.inner.model_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    use super::*;
+
+    fn status(code: u16, retry_after: Option<Duration>) -> anyhow::Error {
+        HttpStatusError {
+            what: "test".to_string(),
+            status: code,
+            retry_after,
+            body: String::new(),
+        }
+        .into()
+    }
+
+    fn quick(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            deadline: None,
+        }
+    }
+
+    #[test]
+    fn test_retries_transient_errors_only() {
+        let calls = Cell::new(0);
+        let result = quick(4).run("op", || {
+            calls.set(calls.get() + 1);
+            match calls.get() {
+                1 => Err(status(429, None)),
+                2 => Err(status(503, None).context("while chatting")),
+                _ => Ok("ok"),
+            }
+        });
+        assert_eq!(result.unwrap(), "ok");
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let result: Result<()> = quick(4).run("op", || {
+            calls.set(calls.get() + 1);
+            Err(status(400, None))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+
+        calls.set(0);
+        let result: Result<()> = quick(3).run("op", || {
+            calls.set(calls.get() + 1);
+            Err(status(500, None))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 3);
+
+        // Timeouts are retried, requests that can't be built aren't.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            // Read the request and never answer it.
+            let (mut conn, _) = listener.accept().unwrap();
+            let _ = conn.read(&mut [0; 1024]);
+            std::thread::sleep(Duration::from_millis(500));
+        });
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let timeout = client.get(&url).send().unwrap_err();
+        assert!(is_retryable(&anyhow::Error::new(timeout)));
+        server.join().unwrap();
+        let unbuildable = client.get("http://[::1/").send().unwrap_err();
+        assert!(!is_retryable(&anyhow::Error::new(unbuildable)));
+    }
+
+    /// Streams one delta, then fails, on the attempts listed in `fail_after_delta`.
+    struct FlakyStream {
+        calls: Cell<u32>,
+        fail_after_delta: Vec<u32>,
+    }
+
+    impl AiClient for FlakyStream {
+        fn chat(&self, _request: ChatRequest) -> Result<ChatResponse> {
+            unreachable!()
+        }
+
+        fn chat_stream(
+            &self,
+            _request: ChatRequest,
+            on_delta: OnDelta<'_>,
+        ) -> Result<ChatResponse> {
+            self.calls.set(self.calls.get() + 1);
+            if self.fail_after_delta.contains(&self.calls.get()) {
+                on_delta(StreamDelta::Content("partial"));
+            }
+            Err(status(503, None))
+        }
+    }
+
+    #[test]
+    fn test_stream_is_not_retried_after_output() {
+        let mut deltas = 0;
+        let client = Retrying::new(
+            FlakyStream {
+                calls: Cell::new(0),
+                fail_after_delta: vec![3],
+            },
+            quick(5),
+        );
+        let result = client.chat_stream(ChatRequest::new(Vec::new()), &mut |_| deltas += 1);
+        assert!(result.is_err());
+        // Two failures before any output are retried, the third isn't.
+        assert_eq!(client.inner.calls.get(), 3);
+        assert_eq!(deltas, 1);
+    }
+
+    #[test]
+    fn test_deadline_and_retry_after() {
+        let policy = RetryPolicy {
+            deadline: Some(Duration::from_secs(5)),
+            ..quick(4)
+        };
+        let calls = Cell::new(0);
+        let err = policy
+            .run("chat", || -> Result<()> {
+                calls.set(calls.get() + 1);
+                Err(status(429, Some(Duration::from_secs(60))))
+            })
+            .unwrap_err();
+        assert_eq!(calls.get(), 1);
+        assert!(err.to_string().contains("gave up after 1 attempts"));
+
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            None // in the past
+        );
+
+        let backoff = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            ..quick(4)
+        };
+        assert!(backoff.backoff(0) <= Duration::from_millis(100));
+        assert!(backoff.backoff(5) >= Duration::from_millis(150));
+        assert!(backoff.backoff(5) <= Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,161 @@
+// crates/core/src/run_stats.rs
+
+//! Per-capability run history.
+//!
+//! Stored by the registry backend (`run_stats.json` under the capabilities
+//! root for the fs backend, a table for SQLite) and used to blend a
+//! reliability/popularity prior into retrieval so a capability that keeps
+//! failing stops outranking one that works.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// File name of the fs backend's stats log under the capabilities root.
+pub const RUN_STATS_FILE: &str = "run_stats.json";
+
+/// Runs after which popularity counts as half saturated.
+const POPULARITY_HALF_RUNS: f32 = 10.0;
+/// Share of the prior given to reliability (the rest is popularity).
+const RELIABILITY_SHARE: f32 = 0.8;
+
+/// Run statistics for a single capability.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CapabilityRunStats {
+    pub success_count: u64,
+    pub failure_count: u64,
+    /// RFC 3339 timestamp of the most recent run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<String>,
+    /// Sum of run latencies, for the mean.
+    pub total_latency_ms: u64,
+}
+
+impl CapabilityRunStats {
+    pub fn runs(&self) -> u64 {
+        self.success_count + self.failure_count
+    }
+
+    pub fn mean_latency_ms(&self) -> Option<f64> {
+        if self.runs() == 0 {
+            None
+        } else {
+            Some(self.total_latency_ms as f64 / self.runs() as f64)
+        }
+    }
+
+    /// Success rate with a Laplace prior, so an unused capability scores 0.5.
+    pub fn reliability(&self) -> f32 {
+        (self.success_count as f32 + 1.0) / (self.runs() as f32 + 2.0)
+    }
+
+    /// Saturating usage count in 0..1.
+    pub fn popularity(&self) -> f32 {
+        let runs = self.runs() as f32;
+        runs / (runs + POPULARITY_HALF_RUNS)
+    }
+
+    /// Combined prior in 0..1 used to adjust retrieval scores.
+    pub fn prior(&self) -> f32 {
+        RELIABILITY_SHARE * self.reliability() + (1.0 - RELIABILITY_SHARE) * self.popularity()
+    }
+
+    /// Count one run that just finished.
+    pub fn record(&mut self, success: bool, latency_ms: u64) {
+        if success {
+            self.success_count += 1;
+        } else {
+            self.failure_count += 1;
+        }
+        self.total_latency_ms += latency_ms;
+        self.last_used = Some(chrono::Utc::now().to_rfc3339());
+    }
+}
+
+/// Prior for a capability in `stats` (the neutral default if it has never run).
+pub fn prior(stats: &HashMap<String, CapabilityRunStats>, capability_id: &str) -> f32 {
+    stats
+        .get(capability_id)
+        .cloned()
+        .unwrap_or_default()
+        .prior()
+}
+
+/// Read a stats file (empty if missing).
+///
+/// Run stats only tune ranking, so a file that can't be parsed (e.g. torn
+/// by a crash) is reported and treated as empty rather than failing.
+pub fn load_file(path: &Path) -> Result<HashMap<String, CapabilityRunStats>> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    };
+
+    match serde_json::from_str(&data) {
+        Ok(stats) => Ok(stats),
+        Err(err) => {
+            eprintln!(
+                "[registry] Warning: ignoring malformed {} ({}), starting with empty run stats",
+                path.display(),
+                err
+            );
+            Ok(HashMap::new())
+        }
+    }
+}
+
+/// Write a stats file via a temporary file and a rename, so a crash
+/// mid-write leaves the old file intact.
+pub fn save_file(path: &Path, stats: &HashMap<String, CapabilityRunStats>) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(stats)? + "\n")
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(success: u64, failure: u64) -> CapabilityRunStats {
+        CapabilityRunStats {
+            success_count: success,
+            failure_count: failure,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prior_orders_by_reliability_then_usage() {
+        let unused = stats(0, 0);
+        let failing = stats(0, 10);
+        let reliable = stats(10, 0);
+
+        assert!((unused.reliability() - 0.5).abs() < 1e-6);
+        assert!(failing.prior() < unused.prior());
+        assert!(reliable.prior() > unused.prior());
+        assert!(stats(50, 0).prior() > reliable.prior());
+    }
+
+    #[test]
+    fn test_malformed_file_is_ignored_and_replaced() {
+        let root = std::env::temp_dir().join(format!("se_run_stats_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join(RUN_STATS_FILE);
+        fs::write(&path, r#"{"get_salary_details": {"success_cou"#).unwrap();
+
+        let mut loaded = load_file(&path).unwrap();
+        assert!(loaded.is_empty());
+
+        loaded.insert("get_salary_details".to_string(), stats(1, 0));
+        save_file(&path, &loaded).unwrap();
+        assert_eq!(load_file(&path).unwrap(), loaded);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+// crates/core/src/scripted_client.rs
+
+//! Chat clients for deterministic, offline runs of the agent loops.
+//!
+//! A cassette is a JSONL file with one `{"request": ..., "response": ...}`
+//! interaction per line. `RecordingClient` wraps a real client and appends
+//! every interaction to a cassette; `ScriptedClient` replays the responses
+//! in order without touching the network.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::ai_client::{
+    AiClient, ChatChoice, ChatMessage, ChatRequest, ChatResponse, ToolCall, ToolFunction,
+};
+use crate::streaming::OnDelta;
+
+/// One request/response pair in a cassette.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The request as sent; informational when replaying.
+    #[serde(default)]
+    pub request: Value,
+    pub response: ChatResponse,
+}
+
+/// Read all interactions from a cassette file.
+pub fn load_cassette(path: &Path) -> Result<Vec<Interaction>> {
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("failed to read cassette {}", path.display()))?;
+
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("failed to parse {} line {}", path.display(), i + 1))
+        })
+        .collect()
+}
+
+/// Replays canned responses in order.
+///
+/// Requests are kept so tests can check what the agent sent.
+pub struct ScriptedClient {
+    responses: RefCell<VecDeque<ChatResponse>>,
+    requests: RefCell<Vec<ChatRequest>>,
+}
+
+impl ScriptedClient {
+    pub fn new(responses: Vec<ChatResponse>) -> Self {
+        Self {
+            responses: RefCell::new(responses.into()),
+            requests: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Replay the responses of a recorded cassette.
+    pub fn from_cassette(path: &Path) -> Result<Self> {
+        Ok(Self::new(
+            load_cassette(path)?
+                .into_iter()
+                .map(|i| i.response)
+                .collect(),
+        ))
+    }
+
+    /// Requests received so far.
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.borrow().clone()
+    }
+
+    /// Responses not yet replayed.
+    pub fn remaining(&self) -> usize {
+        self.responses.borrow().len()
+    }
+}
+
+impl AiClient for ScriptedClient {
+    fn chat(&self, request: ChatRequest) -> Result<ChatResponse> {
+        self.requests.borrow_mut().push(request);
+        self.responses.borrow_mut().pop_front().with_context(|| {
+            format!(
+                "scripted client ran out of responses after {} requests",
+                self.requests.borrow().len() - 1
+            )
+        })
+    }
+}
+
+/// Wraps a client and appends every interaction to a cassette.
+pub struct RecordingClient<C: AiClient> {
+    inner: C,
+    path: PathBuf,
+}
+
+impl<C: AiClient> RecordingClient<C> {
+    pub fn new(inner: C, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+        }
+    }
+}
+
+impl<C: AiClient> RecordingClient<C> {
+    fn record(&self, recorded: Value, response: &ChatResponse) -> Result<()> {
+        let line = serde_json::to_string(&Interaction {
+            request: recorded,
+            response: response.clone(),
+        })?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open cassette {}", self.path.display()))?;
+        writeln!(file, "{}", line)
+            .with_context(|| format!("failed to write cassette {}", self.path.display()))
+    }
+}
+
+impl<C: AiClient> AiClient for RecordingClient<C> {
+    fn chat(&self, request: ChatRequest) -> Result<ChatResponse> {
+        let recorded = serde_json::to_value(&request)?;
+        let response = self.inner.chat(request)?;
+        self.record(recorded, &response)?;
+        Ok(response)
+    }
+
+    fn chat_stream(&self, request: ChatRequest, on_delta: OnDelta<'_>) -> Result<ChatResponse> {
+        let recorded = serde_json::to_value(&request)?;
+        let response = self.inner.chat_stream(request, on_delta)?;
+        self.record(recorded, &response)?;
+        Ok(response)
+    }
+}
+
+/// A response with a final text answer.
+pub fn text_response(content: &str) -> ChatResponse {
+    response(ChatMessage {
+        role: "assistant".to_string(),
+        content: Some(content.to_string()),
+        tool_calls: None,
+    })
+}
+
+/// A response calling one tool with the given arguments.
+pub fn tool_call_response(id: &str, name: &str, arguments: Value) -> ChatResponse {
+    response(ChatMessage {
+        role: "assistant".to_string(),
+        content: None,
+        tool_calls: Some(vec![ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: ToolFunction {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }]),
+    })
+}
+
+fn response(message: ChatMessage) -> ChatResponse {
+    ChatResponse {
+        choices: vec![ChatChoice { message }],
+        usage: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_client::Message;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_then_replay() {
+        let path = std::env::temp_dir().join(format!("se_cassette_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let live = ScriptedClient::new(vec![
+            tool_call_response("call_1", "search_capabilities", json!({"query": "salary"})),
+            text_response("done"),
+        ]);
+        let recorder = RecordingClient::new(live, &path);
+        let question = || ChatRequest::new(vec![Message::user("hi")]);
+        recorder.chat(question()).unwrap();
+        recorder.chat(question()).unwrap();
+
+        let cassette = load_cassette(&path).unwrap();
+        assert_eq!(cassette.len(), 2);
+        assert_eq!(cassette[0].request["messages"][0]["content"], "hi");
+
+        let replay = ScriptedClient::from_cassette(&path).unwrap();
+        let first = replay.chat(question()).unwrap();
+        let call = &first.choices[0].message.tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.function.name, "search_capabilities");
+        assert_eq!(
+            replay.chat(question()).unwrap().choices[0]
+                .message
+                .content
+                .as_deref(),
+            Some("done")
+        );
+        assert!(replay.chat(question()).is_err());
+        assert_eq!(replay.requests().len(), 3);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
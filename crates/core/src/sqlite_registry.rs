@@ -0,0 +1,548 @@
+// crates/core/src/sqlite_registry.rs
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+
+use crate::registry_backend::{
+    transition_refused, CachedEmbedding, RegistryBackend, RegistryChange, RegistryEvent,
+};
+use crate::registry_health::{HealthIssue, IssueKind};
+use crate::run_stats::CapabilityRunStats;
+use crate::types::{CapabilityRecord, CapabilityStatus};
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS capabilities (
+    id TEXT PRIMARY KEY,
+    status TEXT NOT NULL,
+    record TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS history (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    capability_id TEXT NOT NULL,
+    event TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS history_by_capability ON history (capability_id, seq);
+CREATE TABLE IF NOT EXISTS run_stats (
+    capability_id TEXT PRIMARY KEY,
+    success_count INTEGER NOT NULL,
+    failure_count INTEGER NOT NULL,
+    total_latency_ms INTEGER NOT NULL,
+    last_used TEXT
+);
+CREATE TABLE IF NOT EXISTS embeddings (
+    capability_id TEXT PRIMARY KEY,
+    model TEXT NOT NULL,
+    document TEXT NOT NULL,
+    vector TEXT NOT NULL
+);
+"#;
+
+/// Registry backed by a single SQLite database.
+///
+/// Each capability's full record is stored as JSON next to its status; every
+/// change is written together with its history entry in one transaction.
+/// Run stats and cached main-document embeddings have their own tables, and
+/// removing a capability drops its cached embedding in the same transaction.
+pub struct SqliteRegistry {
+    conn: Connection,
+}
+
+impl SqliteRegistry {
+    /// Open (or create) a database file.
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open registry database {}", path))?;
+        Self::init(conn)
+    }
+
+    /// Open a private in-memory database.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)
+            .context("failed to create registry schema")?;
+        Ok(Self { conn })
+    }
+
+    /// True when no capability has been registered yet.
+    pub fn is_empty(&self) -> Result<bool> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM capabilities", [], |row| row.get(0))?;
+        Ok(count == 0)
+    }
+
+    /// Import the records whose id isn't registered yet, leaving existing
+    /// ones untouched. Returns how many were imported.
+    pub fn import_new(&self, records: &[CapabilityRecord]) -> Result<usize> {
+        let mut known = HashSet::new();
+        let mut stmt = self.conn.prepare("SELECT id FROM capabilities")?;
+        for id in stmt.query_map([], |row| row.get::<_, String>(0))? {
+            known.insert(id?);
+        }
+
+        let new: Vec<CapabilityRecord> = records
+            .iter()
+            .filter(|r| !known.contains(&r.id))
+            .cloned()
+            .collect();
+        self.import(&new)?;
+        Ok(new.len())
+    }
+
+    /// Upsert many records in one transaction.
+    pub fn import(&self, records: &[CapabilityRecord]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for record in records {
+            Self::write_record(&tx, record)?;
+            Self::write_event(
+                &tx,
+                &RegistryEvent::now(&record.id, RegistryChange::Upserted, None, Some("imported")),
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Import run stats for capabilities that have none yet, leaving
+    /// existing ones untouched. Returns how many were imported.
+    pub fn import_run_stats(&self, stats: &HashMap<String, CapabilityRunStats>) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut imported = 0;
+        for (id, s) in stats {
+            imported += tx.execute(
+                "INSERT OR IGNORE INTO run_stats
+                     (capability_id, success_count, failure_count, total_latency_ms, last_used)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    id,
+                    s.success_count as i64,
+                    s.failure_count as i64,
+                    s.total_latency_ms as i64,
+                    s.last_used,
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    fn write_record(conn: &Connection, record: &CapabilityRecord) -> Result<()> {
+        conn.execute(
+            "INSERT INTO capabilities (id, status, record, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(id) DO UPDATE SET
+                 status = excluded.status,
+                 record = excluded.record,
+                 updated_at = excluded.updated_at",
+            params![
+                record.id,
+                status_name(&record.status)?,
+                serde_json::to_string(record)?,
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        )
+        .with_context(|| format!("failed to write capability '{}'", record.id))?;
+        Ok(())
+    }
+
+    fn write_event(conn: &Connection, event: &RegistryEvent) -> Result<()> {
+        conn.execute(
+            "INSERT INTO history (capability_id, event) VALUES (?1, ?2)",
+            params![event.capability_id, serde_json::to_string(event)?],
+        )?;
+        Ok(())
+    }
+}
+
+fn status_name(status: &CapabilityStatus) -> Result<String> {
+    Ok(serde_json::to_value(status)?
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+fn parse_record(json: &str) -> Result<CapabilityRecord> {
+    serde_json::from_str(json).context("failed to parse stored capability record")
+}
+
+fn read_run_stats(row: &rusqlite::Row<'_>) -> rusqlite::Result<CapabilityRunStats> {
+    Ok(CapabilityRunStats {
+        success_count: row.get::<_, i64>("success_count")? as u64,
+        failure_count: row.get::<_, i64>("failure_count")? as u64,
+        last_used: row.get("last_used")?,
+        total_latency_ms: row.get::<_, i64>("total_latency_ms")? as u64,
+    })
+}
+
+impl RegistryBackend for SqliteRegistry {
+    fn load(&self) -> Result<Vec<CapabilityRecord>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT record FROM capabilities ORDER BY id")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(parse_record(&row?)?);
+        }
+        Ok(records)
+    }
+
+    fn load_checked(&self) -> Result<(Vec<CapabilityRecord>, Vec<HealthIssue>)> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, record FROM capabilities ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut records = Vec::new();
+        let mut issues = Vec::new();
+        for row in rows {
+            let (id, json) = row?;
+            match serde_json::from_str::<CapabilityRecord>(&json) {
+                Ok(record) if record.id != id => issues.push(HealthIssue::new(
+                    &record.id,
+                    IssueKind::IdMismatch { dir: id },
+                )),
+                Ok(record) => records.push(record),
+                Err(err) => issues.push(HealthIssue::new(
+                    &id,
+                    IssueKind::MalformedMeta(err.to_string()),
+                )),
+            }
+        }
+        Ok((records, issues))
+    }
+
+    fn get(&self, id: &str) -> Result<Option<CapabilityRecord>> {
+        let json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT record FROM capabilities WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        json.as_deref().map(parse_record).transpose()
+    }
+
+    fn upsert(&self, record: &CapabilityRecord) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        Self::write_record(&tx, record)?;
+        Self::write_event(
+            &tx,
+            &RegistryEvent::now(&record.id, RegistryChange::Upserted, None, None),
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn set_status(
+        &self,
+        id: &str,
+        status: CapabilityStatus,
+        allowed: &dyn Fn(&CapabilityStatus) -> bool,
+        replaced_by: Option<&str>,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> Result<CapabilityStatus> {
+        // Take the write lock before reading so no other writer can change
+        // the status between the check and the update.
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+
+        let mut record = match tx
+            .query_row(
+                "SELECT record FROM capabilities WHERE id = ?1",
+                params![id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+        {
+            Some(json) => parse_record(&json)?,
+            None => anyhow::bail!("Capability '{}' not found", id),
+        };
+
+        if !allowed(&record.status) {
+            return Err(transition_refused(id, &record.status, &status));
+        }
+
+        let from = std::mem::replace(&mut record.status, status.clone());
+        if status == CapabilityStatus::Active {
+            record.replaced_by = None;
+            record.deprecated_reason = None;
+        } else if let Some(replacement) = replaced_by {
+            record.replaced_by = Some(replacement.to_string());
+        }
+        if let (CapabilityStatus::Deprecated, Some(reason)) = (&status, reason) {
+            record.deprecated_reason = Some(reason.to_string());
+        }
+
+        Self::write_record(&tx, &record)?;
+        Self::write_event(
+            &tx,
+            &RegistryEvent::now(
+                id,
+                RegistryChange::StatusChanged {
+                    from: from.clone(),
+                    to: status,
+                    replaced_by: replaced_by.map(|r| r.to_string()),
+                },
+                Some(actor),
+                reason,
+            ),
+        )?;
+        tx.commit()?;
+        Ok(from)
+    }
+
+    fn remove(&self, id: &str, actor: &str, reason: Option<&str>) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let deleted = tx.execute("DELETE FROM capabilities WHERE id = ?1", params![id])?;
+        if deleted == 0 {
+            anyhow::bail!("Capability '{}' not found", id);
+        }
+        tx.execute(
+            "DELETE FROM embeddings WHERE capability_id = ?1",
+            params![id],
+        )?;
+        Self::write_event(
+            &tx,
+            &RegistryEvent::now(id, RegistryChange::Removed, Some(actor), reason),
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn history(&self, id: &str) -> Result<Vec<RegistryEvent>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT event FROM history WHERE capability_id = ?1 ORDER BY seq")?;
+        let rows = stmt.query_map(params![id], |row| row.get::<_, String>(0))?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            events
+                .push(serde_json::from_str(&row?).context("failed to parse stored history event")?);
+        }
+        Ok(events)
+    }
+
+    fn run_stats(&self) -> Result<HashMap<String, CapabilityRunStats>> {
+        let mut stmt = self.conn.prepare("SELECT * FROM run_stats")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>("capability_id")?, read_run_stats(row)?))
+        })?;
+
+        let mut stats = HashMap::new();
+        for row in rows {
+            let (id, s) = row?;
+            stats.insert(id, s);
+        }
+        Ok(stats)
+    }
+
+    fn record_run(&self, id: &str, success: bool, latency_ms: u64) -> Result<CapabilityRunStats> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO run_stats
+                 (capability_id, success_count, failure_count, total_latency_ms, last_used)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(capability_id) DO UPDATE SET
+                 success_count = success_count + excluded.success_count,
+                 failure_count = failure_count + excluded.failure_count,
+                 total_latency_ms = total_latency_ms + excluded.total_latency_ms,
+                 last_used = excluded.last_used",
+            params![
+                id,
+                success as i64,
+                !success as i64,
+                latency_ms as i64,
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        )
+        .with_context(|| format!("failed to record a run of '{}'", id))?;
+        let stats = tx.query_row(
+            "SELECT * FROM run_stats WHERE capability_id = ?1",
+            params![id],
+            read_run_stats,
+        )?;
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    fn cached_embedding(&self, id: &str, model: &str, document: &str) -> Result<Option<Vec<f32>>> {
+        let json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT vector FROM embeddings
+                 WHERE capability_id = ?1 AND model = ?2 AND document = ?3",
+                params![id, model, document],
+                |row| row.get(0),
+            )
+            .optional()?;
+        json.map(|v| serde_json::from_str(&v).context("failed to parse stored embedding"))
+            .transpose()
+    }
+
+    fn cache_embeddings(&self, embeddings: &[CachedEmbedding]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for e in embeddings {
+            tx.execute(
+                "INSERT OR REPLACE INTO embeddings (capability_id, model, document, vector)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    e.capability_id,
+                    e.model,
+                    e.document,
+                    serde_json::to_string(&e.vector)?
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str) -> CapabilityRecord {
+        CapabilityRecord {
+            embedding: Some(vec![0.5, 0.5]),
+            binary: Some(format!("{}.wasm", id)),
+            tags: vec!["hr".to_string()],
+            ..CapabilityRecord::new(id, &format!("{} summary", id))
+        }
+    }
+
+    #[test]
+    fn test_upsert_get_and_status_history() {
+        let db = SqliteRegistry::open_in_memory().unwrap();
+        assert!(db.is_empty().unwrap());
+
+        db.import(&[record("get_salary_details")]).unwrap();
+        db.upsert(&record("update_employee_salary")).unwrap();
+        assert_eq!(db.load().unwrap().len(), 2);
+
+        // Only ids the database doesn't have yet are imported.
+        let mut stale = record("get_salary_details");
+        stale.summary = "stale meta.json".to_string();
+        assert_eq!(
+            db.import_new(&[stale, record("get_car_details")]).unwrap(),
+            1
+        );
+        assert_eq!(db.load().unwrap().len(), 3);
+        db.remove("get_car_details", "test", None).unwrap();
+
+        let loaded = db.get("get_salary_details").unwrap().unwrap();
+        assert_eq!(loaded.embedding, Some(vec![0.5, 0.5]));
+        assert_eq!(loaded.tags, vec!["hr"]);
+        assert!(db.get("missing").unwrap().is_none());
+
+        db.set_status(
+            "get_salary_details",
+            CapabilityStatus::Legacy,
+            &|_| true,
+            Some("update_employee_salary"),
+            "test",
+            Some("superseded"),
+        )
+        .unwrap();
+
+        let legacy = db.get("get_salary_details").unwrap().unwrap();
+        assert_eq!(legacy.status, CapabilityStatus::Legacy);
+        assert_eq!(
+            legacy.replaced_by.as_deref(),
+            Some("update_employee_salary")
+        );
+
+        let history = db.history("get_salary_details").unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].change, RegistryChange::Upserted);
+        assert_eq!(
+            history[1].change,
+            RegistryChange::StatusChanged {
+                from: CapabilityStatus::Active,
+                to: CapabilityStatus::Legacy,
+                replaced_by: Some("update_employee_salary".to_string()),
+            }
+        );
+        assert_eq!(history[1].actor.as_deref(), Some("test"));
+        assert_eq!(history[1].reason.as_deref(), Some("superseded"));
+
+        assert!(db
+            .set_status(
+                "missing",
+                CapabilityStatus::Deprecated,
+                &|_| true,
+                None,
+                "test",
+                None
+            )
+            .is_err());
+
+        db.remove("get_salary_details", "test", Some("pruned"))
+            .unwrap();
+        assert!(db.get("get_salary_details").unwrap().is_none());
+        assert_eq!(
+            db.history("get_salary_details").unwrap()[2].change,
+            RegistryChange::Removed
+        );
+        assert!(db.remove("get_salary_details", "test", None).is_err());
+    }
+
+    #[test]
+    fn test_run_stats_and_embedding_cache() {
+        let db = SqliteRegistry::open_in_memory().unwrap();
+        db.upsert(&record("get_salary_details")).unwrap();
+
+        let imported: HashMap<String, CapabilityRunStats> = [(
+            "get_salary_details".to_string(),
+            CapabilityRunStats {
+                success_count: 3,
+                total_latency_ms: 90,
+                ..Default::default()
+            },
+        )]
+        .into_iter()
+        .collect();
+        assert_eq!(db.import_run_stats(&imported).unwrap(), 1);
+        assert_eq!(db.import_run_stats(&imported).unwrap(), 0);
+
+        let updated = db.record_run("get_salary_details", false, 10).unwrap();
+        assert_eq!((updated.success_count, updated.failure_count), (3, 1));
+        assert_eq!(updated.total_latency_ms, 100);
+        assert!(updated.last_used.is_some());
+        db.record_run("get_car_details", true, 5).unwrap();
+        let stats = db.run_stats().unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats["get_salary_details"], updated);
+
+        db.cache_embeddings(&[CachedEmbedding {
+            capability_id: "get_salary_details".to_string(),
+            model: "local:16".to_string(),
+            document: "salary summary".to_string(),
+            vector: vec![0.25, 0.75],
+        }])
+        .unwrap();
+        let cached = |model: &str, doc: &str| {
+            db.cached_embedding("get_salary_details", model, doc)
+                .unwrap()
+        };
+        assert_eq!(cached("local:16", "salary summary"), Some(vec![0.25, 0.75]));
+        // A different document or model isn't a hit.
+        assert_eq!(cached("local:16", "new summary"), None);
+        assert_eq!(cached("openai:bge-m3", "salary summary"), None);
+
+        db.remove("get_salary_details", "test", None).unwrap();
+        assert_eq!(cached("local:16", "salary summary"), None);
+    }
+}
@@ -0,0 +1,289 @@
+// crates/core/src/streaming.rs
+
+//! Streaming chat completions.
+//!
+//! With `"stream": true`, OpenAI-compatible servers (and Foundry) send the
+//! completion as server-sent events, one `chat.completion.chunk` per `data:`
+//! line, ending with `data: [DONE]`. `StreamAssembler` turns the chunks back
+//! into a `ChatResponse`, joining the argument fragments of each tool call,
+//! and reports every fragment as a `StreamDelta` as it arrives.
+
+use std::io::{BufRead, BufReader};
+
+use anyhow::{Context, Result};
+use reqwest::blocking::Response;
+use serde::Deserialize;
+
+use crate::ai_client::{ChatChoice, ChatMessage, ChatResponse, ToolCall, ToolFunction, Usage};
+
+/// A piece of a completion as it streams in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamDelta<'a> {
+    /// Assistant text.
+    Content(&'a str),
+    /// Part of a tool call; `name` is set on the first fragment of a call.
+    ToolCall {
+        index: usize,
+        name: Option<&'a str>,
+        arguments: &'a str,
+    },
+}
+
+/// Callback receiving deltas as they arrive.
+pub type OnDelta<'a> = &'a mut dyn FnMut(StreamDelta<'_>);
+
+/// Report a complete response as deltas, for clients that don't stream.
+pub fn emit_response(response: &ChatResponse, on_delta: OnDelta<'_>) {
+    let Some(choice) = response.choices.first() else {
+        return;
+    };
+    if let Some(content) = choice.message.content.as_deref() {
+        on_delta(StreamDelta::Content(content));
+    }
+    for (index, call) in choice.message.tool_calls.iter().flatten().enumerate() {
+        on_delta(StreamDelta::ToolCall {
+            index,
+            name: Some(&call.function.name),
+            arguments: &call.function.arguments,
+        });
+    }
+}
+
+/// Read a streamed completion from an HTTP response.
+pub fn read_stream(resp: Response, what: &str, on_delta: OnDelta<'_>) -> Result<ChatResponse> {
+    let mut assembler = StreamAssembler::default();
+    read_sse(BufReader::new(resp), |data| {
+        assembler
+            .push(data, on_delta)
+            .with_context(|| format!("failed to parse {} stream chunk: {}", what, data))
+    })
+    .with_context(|| format!("failed to read {} stream", what))?;
+    assembler.finish()
+}
+
+/// Call `on_data` with the payload of each server-sent event until
+/// `[DONE]` or the end of the stream.
+pub fn read_sse(reader: impl BufRead, mut on_data: impl FnMut(&str) -> Result<()>) -> Result<()> {
+    let mut data = String::new();
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if line.is_empty() {
+            // A blank line ends the event.
+            if data == "[DONE]" {
+                return Ok(());
+            }
+            if !data.is_empty() {
+                on_data(&data)?;
+                data.clear();
+            }
+            continue;
+        }
+
+        if let Some(value) = line.strip_prefix("data:") {
+            if !data.is_empty() {
+                data.push('\n');
+            }
+            data.push_str(value.strip_prefix(' ').unwrap_or(value));
+        }
+        // Comments (":") and other fields (event, id, retry) are ignored.
+    }
+
+    if !data.is_empty() && data != "[DONE]" {
+        on_data(&data)?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    // Azure sends content-filter chunks without a delta.
+    #[serde(default)]
+    delta: ChunkDelta,
+}
+
+#[derive(Default, Deserialize)]
+struct ChunkDelta {
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ToolCallDelta>,
+}
+
+#[derive(Deserialize)]
+struct ToolCallDelta {
+    #[serde(default)]
+    index: usize,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    function: Option<FunctionDelta>,
+}
+
+#[derive(Deserialize)]
+struct FunctionDelta {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+}
+
+/// Builds a `ChatResponse` from streamed chunks.
+#[derive(Debug, Default)]
+pub struct StreamAssembler {
+    role: Option<String>,
+    content: String,
+    tool_calls: Vec<ToolCall>,
+    usage: Option<Usage>,
+}
+
+impl StreamAssembler {
+    /// Add one chunk (the JSON payload of a `data:` line).
+    pub fn push(&mut self, data: &str, on_delta: OnDelta<'_>) -> Result<()> {
+        let chunk: ChatChunk = serde_json::from_str(data)?;
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage;
+        }
+
+        for choice in chunk.choices {
+            let delta = choice.delta;
+            if delta.role.is_some() {
+                self.role = delta.role;
+            }
+            if let Some(content) = delta.content.filter(|c| !c.is_empty()) {
+                on_delta(StreamDelta::Content(&content));
+                self.content.push_str(&content);
+            }
+
+            for fragment in delta.tool_calls {
+                while self.tool_calls.len() <= fragment.index {
+                    self.tool_calls.push(ToolCall {
+                        id: String::new(),
+                        call_type: "function".to_string(),
+                        function: ToolFunction {
+                            name: String::new(),
+                            arguments: String::new(),
+                        },
+                    });
+                }
+                let call = &mut self.tool_calls[fragment.index];
+                if let Some(id) = fragment.id {
+                    call.id = id;
+                }
+
+                let (name, arguments) = match fragment.function {
+                    Some(f) => (f.name, f.arguments.unwrap_or_default()),
+                    None => (None, String::new()),
+                };
+                if let Some(name) = &name {
+                    call.function.name.push_str(name);
+                }
+                call.function.arguments.push_str(&arguments);
+
+                on_delta(StreamDelta::ToolCall {
+                    index: fragment.index,
+                    name: name.as_deref(),
+                    arguments: &arguments,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The assembled response.
+    pub fn finish(self) -> Result<ChatResponse> {
+        if let Some(call) = self.tool_calls.iter().find(|c| c.function.name.is_empty()) {
+            anyhow::bail!("streamed tool call '{}' has no function name", call.id);
+        }
+
+        let message = ChatMessage {
+            role: self.role.unwrap_or_else(|| "assistant".to_string()),
+            content: (!self.content.is_empty()).then_some(self.content),
+            tool_calls: (!self.tool_calls.is_empty()).then_some(self.tool_calls),
+        };
+
+        Ok(ChatResponse {
+            choices: vec![ChatChoice { message }],
+            usage: self.usage,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assembles_content_and_tool_call_deltas() {
+        let sse = r#": keep-alive
+
+data: {"choices":[],"prompt_filter_results":[]}
+
+data: {"choices":[{"index":0,"delta":{"role":"assistant","content":"Let me "}}]}
+
+data: {"choices":[{"index":0,"delta":{"content":"check."}}]}
+
+data: {"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"run_capability","arguments":""}}]}}]}
+
+data: {"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"capability_id\":"}}]}}]}
+
+data: {"choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"search_capabilities","arguments":"{}"}}]}}]}
+
+data: {"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":" \"echo\"}"}}]}}]}
+
+data: {"choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}
+
+data: {"choices":[],"usage":{"prompt_tokens":50,"completion_tokens":12,"total_tokens":62}}
+
+data: [DONE]
+
+"#;
+
+        let mut assembler = StreamAssembler::default();
+        let mut seen = Vec::new();
+        read_sse(sse.as_bytes(), |data| {
+            assembler.push(data, &mut |delta| seen.push(format!("{:?}", delta)))
+        })
+        .unwrap();
+        let response = assembler.finish().unwrap();
+
+        let message = &response.choices[0].message;
+        assert_eq!(message.content.as_deref(), Some("Let me check."));
+        let calls = message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_a");
+        assert_eq!(calls[0].function.name, "run_capability");
+        assert_eq!(calls[0].function.arguments, r#"{"capability_id": "echo"}"#);
+        assert_eq!(calls[1].function.name, "search_capabilities");
+        assert_eq!(response.usage.unwrap().total_tokens, 62);
+
+        assert_eq!(seen.len(), 6);
+        assert_eq!(seen[0], r#"Content("Let me ")"#);
+        assert!(seen[2].contains(r#"name: Some("run_capability")"#));
+        assert!(seen[3].contains("name: None"));
+    }
+
+    #[test]
+    fn test_rejects_tool_call_without_name() {
+        let mut assembler = StreamAssembler::default();
+        assembler
+            .push(
+                r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_x","function":{"arguments":"{}"}}]}}]}"#,
+                &mut |_| {},
+            )
+            .unwrap();
+        assert!(assembler.finish().is_err());
+    }
+}
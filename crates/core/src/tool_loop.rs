@@ -0,0 +1,286 @@
+// crates/core/src/tool_loop.rs
+
+//! The tool-calling loop shared by the runtime's agents.
+//!
+//! Each step sends the conversation with the tool definitions, records the
+//! assistant's reply, dispatches its tool calls and appends their results.
+//! What a run produces and when it ends is up to the caller's `LoopHooks`:
+//! a tool result can finish the run (e.g. a successful `complete` call), and
+//! a reply without tool calls can be taken as the answer or answered with a
+//! nudge to keep going.
+//!
+//! With a `ContextPolicy`, tool results are capped as they're added and the
+//! history is compacted before each call so it stays within budget.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::ai_client::{AiClient, ChatResponse, GenerationParams, Message, ToolCall};
+use crate::conversation::{Compaction, ContextPolicy, Conversation, OutputLimit};
+use crate::streaming::StreamDelta;
+
+/// Tools the model can call.
+pub trait ToolSet {
+    /// Tool definitions in the chat completions `tools` format.
+    fn definitions(&self) -> Vec<Value>;
+
+    /// Run a tool call; the returned text is sent back as its result.
+    ///
+    /// Errors abort the run; failures the model should see and recover
+    /// from belong in the returned text.
+    fn dispatch(&mut self, call: &ToolCall) -> Result<String>;
+
+    /// How much of a tool's output to keep in the history; `None` uses the
+    /// policy's default.
+    fn output_limit(&self, _tool: &str) -> Option<OutputLimit> {
+        None
+    }
+}
+
+/// Whether the loop keeps going.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Control<T> {
+    Continue,
+    Stop(T),
+}
+
+/// Callbacks that observe the loop and decide when it stops.
+pub trait LoopHooks {
+    /// What a finished run returns.
+    type Output;
+
+    /// Before each model call (0-based); an error aborts the run, e.g.
+    /// when a budget is spent.
+    fn on_step(&mut self, _step: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Streamed text and tool-call arguments as they arrive.
+    fn on_delta(&mut self, _delta: StreamDelta<'_>) {}
+
+    /// The history was compacted before a call.
+    fn on_compaction(&mut self, _compaction: &Compaction) {}
+
+    /// Each complete model response.
+    fn on_response(&mut self, _response: &ChatResponse) {}
+
+    /// After a tool result has been added to the conversation.
+    fn on_tool_result(&mut self, _call: &ToolCall, _result: &str) -> Result<Control<Self::Output>> {
+        Ok(Control::Continue)
+    }
+
+    /// The model replied without calling a tool. To continue, the hook
+    /// adds whatever should follow to the conversation.
+    fn on_text(
+        &mut self,
+        content: Option<String>,
+        conversation: &mut Conversation,
+    ) -> Result<Control<Self::Output>>;
+}
+
+/// Runs the step loop against a chat client.
+pub struct ToolLoop<'a, C: AiClient + ?Sized> {
+    client: &'a C,
+    max_steps: usize,
+    params: GenerationParams,
+    context: Option<ContextPolicy>,
+}
+
+impl<'a, C: AiClient + ?Sized> ToolLoop<'a, C> {
+    pub fn new(client: &'a C, max_steps: usize) -> Self {
+        Self {
+            client,
+            max_steps,
+            params: GenerationParams::default(),
+            context: None,
+        }
+    }
+
+    pub fn with_params(mut self, params: GenerationParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Cap tool outputs and compact the history to fit `policy`.
+    pub fn with_context_policy(mut self, policy: ContextPolicy) -> Self {
+        self.context = Some(policy);
+        self
+    }
+
+    /// Run until a hook stops the loop, or `None` after `max_steps`.
+    pub fn run<H: ToolSet + LoopHooks>(
+        &self,
+        conversation: &mut Conversation,
+        hooks: &mut H,
+    ) -> Result<Option<H::Output>> {
+        let definitions = hooks.definitions();
+
+        for step in 0..self.max_steps {
+            hooks.on_step(step)?;
+
+            if let Some(policy) = &self.context {
+                let compaction = conversation.compact(policy);
+                if compaction.changed() {
+                    hooks.on_compaction(&compaction);
+                }
+            }
+
+            let request = conversation
+                .request()
+                .with_tools(definitions.clone())
+                .with_params(&self.params);
+            let response = self
+                .client
+                .chat_stream(request, &mut |delta| hooks.on_delta(delta))?;
+            hooks.on_response(&response);
+
+            let message = response
+                .choices
+                .into_iter()
+                .next()
+                .context("no choices in chat response")?
+                .message;
+
+            let calls = message.tool_calls.clone().unwrap_or_default();
+            if calls.is_empty() {
+                if let Control::Stop(output) = hooks.on_text(message.content, conversation)? {
+                    return Ok(Some(output));
+                }
+                continue;
+            }
+
+            conversation.push(Message::from(message));
+            for call in &calls {
+                let result = hooks.dispatch(call)?;
+                match &self.context {
+                    Some(policy) => {
+                        let limit = hooks
+                            .output_limit(&call.function.name)
+                            .unwrap_or(policy.default_output_limit);
+                        conversation.push_tool_result(call, limit.apply(&result));
+                    }
+                    None => conversation.push_tool_result(call, result.as_str()),
+                }
+                if let Control::Stop(output) = hooks.on_tool_result(call, &result)? {
+                    return Ok(Some(output));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::conversation::Keep;
+    use crate::scripted_client::{text_response, tool_call_response, ScriptedClient};
+
+    /// A counter tool that finishes when asked to, nudging once on text.
+    #[derive(Default)]
+    struct Counter {
+        total: i64,
+        steps: usize,
+        nudged: bool,
+    }
+
+    impl ToolSet for Counter {
+        fn definitions(&self) -> Vec<Value> {
+            vec![json!({"type": "function", "function": {"name": "add"}})]
+        }
+
+        fn dispatch(&mut self, call: &ToolCall) -> Result<String> {
+            let args: Value = serde_json::from_str(&call.function.arguments)?;
+            self.total += args["n"].as_i64().context("missing n")?;
+            Ok(format!("total = {}", self.total))
+        }
+    }
+
+    impl LoopHooks for Counter {
+        type Output = i64;
+
+        fn on_step(&mut self, step: usize) -> Result<()> {
+            self.steps = step + 1;
+            Ok(())
+        }
+
+        fn on_tool_result(&mut self, _call: &ToolCall, result: &str) -> Result<Control<i64>> {
+            Ok(match result {
+                "total = 10" => Control::Stop(self.total),
+                _ => Control::Continue,
+            })
+        }
+
+        fn on_text(
+            &mut self,
+            _content: Option<String>,
+            conversation: &mut Conversation,
+        ) -> Result<Control<i64>> {
+            if self.nudged {
+                return Ok(Control::Stop(-1));
+            }
+            self.nudged = true;
+            conversation.push(Message::user("keep adding"));
+            Ok(Control::Continue)
+        }
+    }
+
+    #[test]
+    fn test_loop_dispatches_tools_until_a_hook_stops_it() {
+        let client = ScriptedClient::new(vec![
+            tool_call_response("call_1", "add", json!({"n": 3})),
+            text_response("thinking"),
+            tool_call_response("call_2", "add", json!({"n": 7})),
+        ]);
+        let mut conversation = Conversation::new("rules", "count to 10");
+        let mut counter = Counter::default();
+
+        let output = ToolLoop::new(&client, 5)
+            .run(&mut conversation, &mut counter)
+            .unwrap();
+        assert_eq!(output, Some(10));
+        assert_eq!(counter.steps, 3);
+
+        // preamble, call + result, nudge, call + result
+        assert_eq!(conversation.len(), 7);
+        assert_eq!(conversation.messages()[4].content(), Some("keep adding"));
+        let requests = client.requests();
+        assert_eq!(requests[0].tools.len(), 1);
+        assert_eq!(requests[2].messages.len(), 5);
+
+        let client = ScriptedClient::new(vec![
+            tool_call_response("call_1", "add", json!({"n": 1})),
+            tool_call_response("call_2", "add", json!({"n": 1})),
+        ]);
+        let mut conversation = Conversation::new("rules", "count to 10");
+        let output = ToolLoop::new(&client, 2)
+            .run(&mut conversation, &mut Counter::default())
+            .unwrap();
+        assert_eq!(output, None);
+    }
+
+    #[test]
+    fn test_context_policy_caps_tool_results() {
+        let client =
+            ScriptedClient::new(vec![tool_call_response("call_1", "add", json!({"n": 10}))]);
+        let mut conversation = Conversation::new("rules", "count to 10");
+        let policy = ContextPolicy {
+            default_output_limit: OutputLimit::new(5, Keep::Head),
+            ..ContextPolicy::default()
+        };
+
+        let output = ToolLoop::new(&client, 1)
+            .with_context_policy(policy)
+            .run(&mut conversation, &mut Counter::default())
+            .unwrap();
+        // Hooks see the full result; the history keeps the capped one.
+        assert_eq!(output, Some(10));
+        assert_eq!(
+            conversation.messages()[3].content(),
+            Some("total\n... [5 chars truncated] ...\n")
+        );
+    }
+}
@@ -14,6 +14,8 @@ pub enum CapabilityStatus {
     Legacy,
     /// No longer functional or supported.
     Deprecated,
+    /// Deliberately withdrawn for good; never revived.
+    Retired,
 }
 
 /// Capability metadata as seen by the embedding/index layer.
@@ -23,7 +25,8 @@ pub struct CapabilityRecord {
     pub id: CapabilityId,
     /// Human-readable summary of what the capability does.
     pub summary: String,
-    /// Optional cached embedding (all embeddings must share the same dimension).
+    /// Optional cached embedding of the main index document
+    /// (all embeddings must share the same dimension).
     pub embedding: Option<Vec<f32>>,
     /// Relative path to the capability binary (e.g. "bin.wasm" or "bin").
     pub binary: Option<String>,
@@ -33,9 +36,42 @@ pub struct CapabilityRecord {
     /// If this capability was replaced, the ID of its replacement.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub replaced_by: Option<String>,
+    /// Why it was deprecated; cleared when it becomes active again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated_reason: Option<String>,
+    /// JSON Schema of the stdin input, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<serde_json::Value>,
+    /// JSON Schema of the stdout output, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,
+    /// Free-form tags (domain, read/write, ...) included in the index document.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Tasks this capability has solved, included in the index document.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub example_tasks: Vec<String>,
 }
 
 impl CapabilityRecord {
+    /// An active capability with only an id and summary; fill in the rest
+    /// with struct update syntax.
+    pub fn new(id: &str, summary: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            summary: summary.to_string(),
+            embedding: None,
+            binary: None,
+            status: CapabilityStatus::Active,
+            replaced_by: None,
+            deprecated_reason: None,
+            input_schema: None,
+            output_schema: None,
+            tags: Vec::new(),
+            example_tasks: Vec::new(),
+        }
+    }
+
     /// Check if this capability is active (not legacy or deprecated).
     pub fn is_active(&self) -> bool {
         self.status == CapabilityStatus::Active
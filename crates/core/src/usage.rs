@@ -0,0 +1,312 @@
+// crates/core/src/usage.rs
+
+//! Token usage accounting and budgets for agent runs.
+//!
+//! A `UsageMeter` adds up the `usage` block of every chat response in a run
+//! (an agent task or a mutation), prices it if per-token prices are
+//! configured, and reports `BudgetExceeded` once a token or cost limit is
+//! reached so the run can stop before making another call.
+
+use std::fmt;
+
+use anyhow::{Context, Result};
+
+use crate::ai_client::{ChatResponse, ChatRole, Usage};
+
+/// Price of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+impl Pricing {
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt_per_million
+            + usage.completion_tokens as f64 * self.completion_per_million)
+            / 1_000_000.0
+    }
+
+    /// Read prices for a role, or `None` when they aren't configured.
+    ///
+    /// - CHAT_PROMPT_PRICE, CHAT_COMPLETION_PRICE: USD per million tokens
+    ///
+    /// The mutation agent reads MUTATION_CHAT_PROMPT_PRICE and
+    /// MUTATION_CHAT_COMPLETION_PRICE first, like MUTATION_CHAT_PROVIDER.
+    pub fn from_env(role: ChatRole) -> Result<Option<Self>> {
+        Self::from_vars(role, |name| std::env::var(name).ok())
+    }
+
+    /// `from_env` with variables looked up through `vars`.
+    pub fn from_vars(
+        role: ChatRole,
+        vars: impl Fn(&str) -> Option<String>,
+    ) -> Result<Option<Self>> {
+        let read = |name: &str| -> Result<Option<f64>> {
+            let value = match role {
+                ChatRole::Agent => None,
+                ChatRole::Mutation => vars(&format!("MUTATION_{}", name)),
+            }
+            .or_else(|| vars(name));
+
+            value
+                .map(|v| {
+                    v.parse()
+                        .with_context(|| format!("invalid {} '{}'", name, v))
+                })
+                .transpose()
+        };
+
+        match (read("CHAT_PROMPT_PRICE")?, read("CHAT_COMPLETION_PRICE")?) {
+            (None, None) => Ok(None),
+            (prompt, completion) => Ok(Some(Self {
+                prompt_per_million: prompt.unwrap_or(0.0),
+                completion_per_million: completion.unwrap_or(0.0),
+            })),
+        }
+    }
+}
+
+/// Limits for a single run; unset limits are unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    pub max_tokens: Option<u64>,
+    /// USD; needs `Pricing`.
+    pub max_cost: Option<f64>,
+}
+
+impl Budget {
+    /// Read limits from `<prefix>_TOKEN_BUDGET` and `<prefix>_COST_BUDGET`.
+    pub fn from_env(prefix: &str) -> Result<Self> {
+        Self::from_vars(prefix, |name| std::env::var(name).ok())
+    }
+
+    /// `from_env` with variables looked up through `vars`.
+    pub fn from_vars(prefix: &str, vars: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let tokens_var = format!("{}_TOKEN_BUDGET", prefix);
+        let cost_var = format!("{}_COST_BUDGET", prefix);
+
+        let max_tokens = match vars(&tokens_var) {
+            Some(v) => Some(
+                v.parse()
+                    .with_context(|| format!("invalid {} '{}'", tokens_var, v))?,
+            ),
+            None => None,
+        };
+        let max_cost = match vars(&cost_var) {
+            Some(v) => Some(
+                v.parse()
+                    .with_context(|| format!("invalid {} '{}'", cost_var, v))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            max_tokens,
+            max_cost,
+        })
+    }
+}
+
+/// Usage added up over a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UsageTotals {
+    pub calls: u32,
+    /// Calls whose response had no `usage` block.
+    pub unreported: u32,
+    pub usage: Usage,
+    /// USD, when prices are configured.
+    pub cost: Option<f64>,
+}
+
+impl fmt::Display for UsageTotals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} calls, {} tokens ({} prompt + {} completion)",
+            self.calls,
+            self.usage.total_tokens,
+            self.usage.prompt_tokens,
+            self.usage.completion_tokens
+        )?;
+        if let Some(cost) = self.cost {
+            write!(f, ", ${:.4}", cost)?;
+        }
+        if self.unreported > 0 {
+            write!(f, ", {} calls without usage", self.unreported)?;
+        }
+        Ok(())
+    }
+}
+
+/// A run stopped because it reached its budget.
+#[derive(Debug, Clone)]
+pub struct BudgetExceeded {
+    pub limit: String,
+    pub totals: UsageTotals,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "budget of {} exceeded after {}", self.limit, self.totals)
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Running usage and budget for one run.
+#[derive(Debug, Clone, Default)]
+pub struct UsageMeter {
+    pricing: Option<Pricing>,
+    budget: Budget,
+    totals: UsageTotals,
+}
+
+impl UsageMeter {
+    pub fn new(pricing: Option<Pricing>, budget: Budget) -> Result<Self> {
+        if budget.max_cost.is_some() && pricing.is_none() {
+            anyhow::bail!("a cost budget needs prices to be set");
+        }
+
+        Ok(Self {
+            pricing,
+            budget,
+            totals: UsageTotals {
+                cost: pricing.map(|_| 0.0),
+                ..UsageTotals::default()
+            },
+        })
+    }
+
+    /// Meter for a role: the agent's budget is per task (TASK_*), the
+    /// mutation agent's per mutation (MUTATION_*).
+    pub fn from_env(role: ChatRole) -> Result<Self> {
+        Self::from_vars(role, |name| std::env::var(name).ok())
+    }
+
+    /// `from_env` with variables looked up through `vars`.
+    pub fn from_vars(role: ChatRole, vars: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let (prefix, prices) = match role {
+            ChatRole::Agent => ("TASK", "CHAT_PROMPT_PRICE and CHAT_COMPLETION_PRICE"),
+            ChatRole::Mutation => (
+                "MUTATION",
+                "MUTATION_CHAT_PROMPT_PRICE and MUTATION_CHAT_COMPLETION_PRICE (or CHAT_*_PRICE)",
+            ),
+        };
+        Self::new(
+            Pricing::from_vars(role, &vars)?,
+            Budget::from_vars(prefix, &vars)?,
+        )
+        .with_context(|| format!("{}_COST_BUDGET is set but {} are not", prefix, prices))
+    }
+
+    /// Add one chat response.
+    pub fn record(&mut self, response: &ChatResponse) {
+        self.totals.calls += 1;
+        match &response.usage {
+            Some(usage) => {
+                self.totals.usage += *usage;
+                if let (Some(cost), Some(pricing)) = (&mut self.totals.cost, &self.pricing) {
+                    *cost += pricing.cost(usage);
+                }
+            }
+            None => self.totals.unreported += 1,
+        }
+    }
+
+    /// Add the totals of a nested run, e.g. a mutation started by the agent.
+    pub fn absorb(&mut self, other: &UsageTotals) {
+        self.totals.calls += other.calls;
+        self.totals.unreported += other.unreported;
+        self.totals.usage += other.usage;
+        if let (Some(cost), Some(other)) = (&mut self.totals.cost, other.cost) {
+            *cost += other;
+        }
+    }
+
+    pub fn totals(&self) -> UsageTotals {
+        self.totals
+    }
+
+    /// Fail with `BudgetExceeded` once a limit has been reached.
+    pub fn check(&self) -> Result<(), BudgetExceeded> {
+        let exceeded = |limit: String| BudgetExceeded {
+            limit,
+            totals: self.totals,
+        };
+
+        if let Some(max) = self.budget.max_tokens {
+            if self.totals.usage.total_tokens >= max {
+                return Err(exceeded(format!("{} tokens", max)));
+            }
+        }
+        if let (Some(max), Some(cost)) = (self.budget.max_cost, self.totals.cost) {
+            if cost >= max {
+                return Err(exceeded(format!("${:.4}", max)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(json: &str) -> ChatResponse {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_meter_prices_usage_and_enforces_budget() {
+        let priced = response(
+            r#"{"choices": [], "usage": {"prompt_tokens": 1000, "completion_tokens": 200, "total_tokens": 1200}}"#,
+        );
+        let unpriced = response(r#"{"choices": []}"#);
+
+        let pricing = Pricing {
+            prompt_per_million: 2.5,
+            completion_per_million: 10.0,
+        };
+        let budget = Budget {
+            max_tokens: Some(3000),
+            max_cost: None,
+        };
+        let mut meter = UsageMeter::new(Some(pricing), budget).unwrap();
+
+        meter.record(&priced);
+        meter.record(&unpriced);
+        assert!(meter.check().is_ok());
+        let totals = meter.totals();
+        assert_eq!(totals.calls, 2);
+        assert_eq!(totals.unreported, 1);
+        assert_eq!(totals.usage.total_tokens, 1200);
+        assert!((totals.cost.unwrap() - 0.0045).abs() < 1e-9);
+
+        let mut mutation = UsageMeter::new(Some(pricing), Budget::default()).unwrap();
+        mutation.record(&priced);
+        mutation.record(&priced);
+        meter.absorb(&mutation.totals());
+        let err = meter.check().unwrap_err();
+        assert_eq!(err.totals.usage.total_tokens, 3600);
+        assert!(err
+            .to_string()
+            .starts_with("budget of 3000 tokens exceeded"));
+
+        let cost_only = Budget {
+            max_tokens: None,
+            max_cost: Some(0.004),
+        };
+        assert!(UsageMeter::new(None, cost_only).is_err());
+        let budget_only = |name: &str| (name == "MUTATION_COST_BUDGET").then(|| "0.25".to_string());
+        let err = format!(
+            "{:#}",
+            UsageMeter::from_vars(ChatRole::Mutation, budget_only).unwrap_err()
+        );
+        assert!(err.starts_with("MUTATION_COST_BUDGET is set but MUTATION_CHAT_PROMPT_PRICE"));
+        let mut meter = UsageMeter::new(Some(pricing), cost_only).unwrap();
+        meter.record(&priced);
+        assert!(meter.check().is_err());
+    }
+}
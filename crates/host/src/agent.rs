@@ -3,12 +3,29 @@
 use anyhow::{Context, Result};
 use serde_json::json;
 
-use se_runtime_core::ai_client::{AiClient, ChatRequest, ChatToolCall};
+use se_runtime_core::ai_client::{AiClient, ChatResponse, GenerationParams, ToolCall};
 use se_runtime_core::capability_runner::CapabilityRunner;
+use se_runtime_core::conversation::{Compaction, ContextPolicy, Conversation};
 use se_runtime_core::embedding::Embedder;
+use se_runtime_core::streaming::StreamDelta;
+use se_runtime_core::tool_loop::{Control, LoopHooks, ToolLoop, ToolSet};
+use se_runtime_core::usage::{BudgetExceeded, UsageMeter};
 
 use crate::mutation_agent::MutationAgent;
 use crate::store::CapabilityStore;
+use crate::stream_printer::StreamPrinter;
+
+/// Default number of results for search_capabilities.
+const DEFAULT_SEARCH_K: usize = 3;
+/// Upper bound on search_capabilities results.
+const MAX_SEARCH_K: usize = 10;
+/// Default generation settings, overridable with AGENT_* variables.
+const AGENT_PARAMS: GenerationParams = GenerationParams {
+    temperature: Some(0.2),
+    max_tokens: None,
+    seed: None,
+    parallel_tool_calls: None,
+};
 
 /// The agent orchestrates the agentic loop: sending tasks to the LLM,
 /// handling tool calls, and returning a final answer.
@@ -22,6 +39,14 @@ pub struct Agent<'a, C: AiClient, M: AiClient, E: Embedder> {
     max_steps: usize,
     /// Track failures per capability to avoid repeated deprecation
     failure_counts: std::collections::HashMap<String, usize>,
+    /// Successful (capability_id, input_json) runs in the current task,
+    /// recorded as examples once the task gets a final answer.
+    successful_runs: Vec<(String, String)>,
+    /// Token usage of the current task, including its mutations.
+    usage: UsageMeter,
+    /// The task being solved, for recording examples.
+    task: String,
+    printer: StreamPrinter,
 }
 
 impl<'a, C: AiClient, M: AiClient, E: Embedder> Agent<'a, C, M, E> {
@@ -42,18 +67,43 @@ impl<'a, C: AiClient, M: AiClient, E: Embedder> Agent<'a, C, M, E> {
             capabilities_root,
             max_steps: 12,
             failure_counts: std::collections::HashMap::new(),
+            successful_runs: Vec::new(),
+            usage: UsageMeter::default(),
+            task: String::new(),
+            printer: StreamPrinter::default(),
         }
     }
 
     /// Run the agentic loop for a given task.
-    pub fn run_task(&mut self, task: &str, capabilities_summary: &str) -> Result<String> {
-        let tools = self.tool_definitions();
+    ///
+    /// `usage` is the task's meter (`UsageMeter::from_env(ChatRole::Agent)`),
+    /// already charged for retrieval. Stops before the next model call once
+    /// the task's budget (TASK_TOKEN_BUDGET, TASK_COST_BUDGET) is spent, and
+    /// reports the task's token usage.
+    pub fn run_task(
+        &mut self,
+        task: &str,
+        capabilities_summary: &str,
+        usage: UsageMeter,
+    ) -> Result<String> {
+        self.usage = usage;
+        let result = self.agent_loop(task, capabilities_summary);
+        println!("[USAGE] Task: {}", self.usage.totals());
+        result
+    }
+
+    fn agent_loop(&mut self, task: &str, capabilities_summary: &str) -> Result<String> {
+        let params = GenerationParams::from_env("AGENT", AGENT_PARAMS)?;
+        let context = ContextPolicy::from_env("AGENT")?;
+        self.successful_runs.clear();
+        self.task = task.to_string();
 
         let system_prompt = format!(
             "You are an agent that MUST solve tasks using executable capabilities.\n\
              You are given a list of capabilities (id and summary).\n\
              RULES:\n\
              - Use run_capability to execute an existing capability.\n\
+             - If you need something the listed capabilities don't cover, use search_capabilities to look for an existing one first.\n\
              - If no capability exists for what you need, use mutate_capability to create one.\n\
              - After mutating, you can immediately run_capability with the new id.\n\n\
              {}",
@@ -62,79 +112,26 @@ impl<'a, C: AiClient, M: AiClient, E: Embedder> Agent<'a, C, M, E> {
 
         println!("system_prompt:\n{}", system_prompt);
 
-        let mut messages = vec![
-            json!({ "role": "system", "content": system_prompt }),
-            json!({ "role": "user", "content": task }),
-        ];
-
-        for step in 0..self.max_steps {
-            println!("\n[AGENT STEP {}]", step + 1);
-
-            let request = ChatRequest::new(messages.clone()).with_tools(tools.clone());
-            let response = self.client.chat(request)?;
-
-            let choice = response
-                .choices
-                .into_iter()
-                .next()
-                .context("no choices in chat response")?;
-
-            let msg = choice.message;
-
-            // If there are tool calls, handle them
-            if let Some(tool_calls) = msg.tool_calls.clone() {
-                println!("[ASSISTANT TOOL CALLS]");
-
-                // Push the assistant message with tool_calls into history
-                let assistant_msg = json!({
-                    "role": "assistant",
-                    "content": msg.content.clone(),
-                    "tool_calls": tool_calls.iter().map(|tc| {
-                        json!({
-                            "id": tc.id,
-                            "type": tc.call_type,
-                            "function": {
-                                "name": tc.function.name,
-                                "arguments": tc.function.arguments,
-                            }
-                        })
-                    }).collect::<Vec<_>>()
-                });
-                messages.push(assistant_msg);
-
-                // Run each tool and append results
-                for tc in tool_calls {
-                    let result = self.handle_tool_call(&tc)?;
-                    messages.push(json!({
-                        "role": "tool",
-                        "tool_call_id": tc.id,
-                        "name": tc.function.name,
-                        "content": result,
-                    }));
-                }
-
-                continue;
-            }
-
-            // No tool calls => final answer
-            let content = msg.content.unwrap_or_else(|| "<no content>".to_string());
-            println!("[AGENT FINAL]");
-            println!("{content}");
-            return Ok(content);
-        }
+        let mut conversation = Conversation::new(system_prompt, task);
 
-        anyhow::bail!("Agentic loop reached max_steps without a final answer")
+        let client = self.client;
+        ToolLoop::new(client, self.max_steps)
+            .with_params(params)
+            .with_context_policy(context)
+            .run(&mut conversation, self)?
+            .context("Agentic loop reached max_steps without a final answer")
     }
 
-    fn handle_tool_call(&mut self, tc: &ChatToolCall) -> Result<String> {
-        match tc.function.name.as_str() {
-            "run_capability" => self.handle_run_capability(tc),
-            "mutate_capability" => self.handle_mutate_capability(tc),
-            other => anyhow::bail!("Unknown tool: {}", other),
+    /// Attach the task to every capability that ran successfully while solving it.
+    fn record_examples(&mut self, task: &str) {
+        for (capability_id, input_json) in std::mem::take(&mut self.successful_runs) {
+            if let Err(e) = self.store.record_example(task, &capability_id, &input_json) {
+                println!("[AGENT] Warning: Failed to record example: {}", e);
+            }
         }
     }
 
-    fn handle_run_capability(&mut self, tc: &ChatToolCall) -> Result<String> {
+    fn handle_run_capability(&mut self, tc: &ToolCall) -> Result<String> {
         println!("[TOOL CALL] run_capability");
 
         let args: serde_json::Value = serde_json::from_str(&tc.function.arguments)
@@ -159,10 +156,23 @@ impl<'a, C: AiClient, M: AiClient, E: Embedder> Agent<'a, C, M, E> {
             .with_context(|| format!("Requested capability_id '{}' not found", capability_id))?
             .clone();
 
-        match self.runner.run_capability(&cap, input_json) {
+        let started = std::time::Instant::now();
+        let result = self.runner.run_capability(&cap, input_json);
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        if let Err(e) = self
+            .store
+            .record_run(capability_id, result.is_ok(), latency_ms)
+        {
+            println!("[AGENT] Warning: Failed to record run stats: {}", e);
+        }
+
+        match result {
             Ok(output) => {
                 // Reset failure count on success
                 self.failure_counts.remove(capability_id);
+                self.successful_runs
+                    .push((capability_id.to_string(), input_json.to_string()));
                 println!("[TOOL OUTPUT]");
                 println!("{output}");
                 Ok(output)
@@ -181,11 +191,10 @@ impl<'a, C: AiClient, M: AiClient, E: Embedder> Agent<'a, C, M, E> {
                 if *count >= 2 {
                     let deprecation_reason =
                         format!("Failed {} times. Last error: {}", count, error_msg);
-                    if let Err(dep_err) = self.store.mark_deprecated(
-                        self.capabilities_root,
-                        capability_id,
-                        &deprecation_reason,
-                    ) {
+                    if let Err(dep_err) = self
+                        .store
+                        .mark_deprecated(capability_id, &deprecation_reason)
+                    {
                         println!(
                             "[AGENT] Warning: Failed to mark capability as deprecated: {}",
                             dep_err
@@ -202,7 +211,7 @@ impl<'a, C: AiClient, M: AiClient, E: Embedder> Agent<'a, C, M, E> {
         }
     }
 
-    fn handle_mutate_capability(&mut self, tc: &ChatToolCall) -> Result<String> {
+    fn handle_mutate_capability(&mut self, tc: &ToolCall) -> Result<String> {
         println!("[TOOL CALL] mutate_capability");
 
         let args: serde_json::Value = serde_json::from_str(&tc.function.arguments)
@@ -222,8 +231,26 @@ impl<'a, C: AiClient, M: AiClient, E: Embedder> Agent<'a, C, M, E> {
         println!("  parent_capability_id = {}", parent_id);
 
         // Spawn mutation agent with the dedicated mutation client
-        let mut mutation_agent = MutationAgent::new(self.mutation_client, self.capabilities_root);
-        let result = mutation_agent.mutate_capability(task_description, parent_id)?;
+        let mut mutation_agent = MutationAgent::new(
+            self.mutation_client,
+            self.capabilities_root,
+            self.store.registry(),
+        );
+        let result = mutation_agent.mutate_capability(task_description, parent_id);
+        self.usage.absorb(&mutation_agent.usage());
+        let result = match result {
+            // Let the agent carry on with what it has instead of failing the task.
+            Err(e) if e.downcast_ref::<BudgetExceeded>().is_some() => {
+                let output = format!(
+                    "ERROR: Mutation stopped, {}. No capability was created; solve the task with the existing capabilities or explain why it can't be done.",
+                    e
+                );
+                println!("[TOOL OUTPUT]");
+                println!("{output}");
+                return Ok(output);
+            }
+            result => result?,
+        };
 
         // Reload the store to pick up the new capability
         println!("[AGENT] Reloading capability store...");
@@ -240,6 +267,74 @@ impl<'a, C: AiClient, M: AiClient, E: Embedder> Agent<'a, C, M, E> {
         Ok(output)
     }
 
+    fn handle_search_capabilities(&mut self, tc: &ToolCall) -> Result<String> {
+        println!("[TOOL CALL] search_capabilities");
+
+        let args: serde_json::Value = serde_json::from_str(&tc.function.arguments)
+            .context("failed to parse search_capabilities.arguments as JSON")?;
+
+        let query = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .context("search_capabilities.arguments missing 'query'")?;
+
+        let k = args
+            .get("k")
+            .and_then(|v| v.as_u64())
+            .map(|k| k as usize)
+            .unwrap_or(DEFAULT_SEARCH_K)
+            .clamp(1, MAX_SEARCH_K);
+
+        println!("  query = {}", query);
+        println!("  k     = {}", k);
+
+        let results = self.store.rank_for_task(query, self.embedder, k)?;
+
+        let output = if results.is_empty() {
+            "No active capabilities found.".to_string()
+        } else {
+            let mut lines = vec![format!(
+                "Found {} capabilities for '{}' (best first):",
+                results.len(),
+                query
+            )];
+            for (id, score) in &results {
+                if let Some(cap) = self.store.get_capability(id) {
+                    lines.push(format!(
+                        "- id: {}\n  score: {:.3}\n  summary: {}",
+                        cap.id, score, cap.summary
+                    ));
+                    if let Some(schema) = &cap.input_schema {
+                        lines.push(format!("  input_schema: {}", schema));
+                    }
+                    if let Some(schema) = &cap.output_schema {
+                        lines.push(format!("  output_schema: {}", schema));
+                    }
+                    if let Some(stats) = self.store.run_stats(id) {
+                        lines.push(format!(
+                            "  history: {} runs, {} failed, mean {:.0} ms",
+                            stats.runs(),
+                            stats.failure_count,
+                            stats.mean_latency_ms().unwrap_or(0.0)
+                        ));
+                    }
+                    if let Some(example) = self.store.examples(id).last() {
+                        lines.push(format!(
+                            "  example: task {:?} -> input_json {}",
+                            example.task, example.input_json
+                        ));
+                    }
+                }
+            }
+            lines.join("\n")
+        };
+
+        println!("[TOOL OUTPUT]");
+        println!("{output}");
+
+        Ok(output)
+    }
+
     fn tool_definitions(&self) -> Vec<serde_json::Value> {
         vec![
             json!({
@@ -284,6 +379,189 @@ impl<'a, C: AiClient, M: AiClient, E: Embedder> Agent<'a, C, M, E> {
                     }
                 }
             }),
+            json!({
+                "type": "function",
+                "function": {
+                    "name": "search_capabilities",
+                    "description": "Search all existing capabilities by meaning. Use this when a step of the task needs something the listed capabilities don't cover, BEFORE deciding to mutate.",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {
+                            "query": {
+                                "type": "string",
+                                "description": "What the capability should do, e.g. 'employee dental and vision benefits'."
+                            },
+                            "k": {
+                                "type": "integer",
+                                "description": "Maximum number of results (default 3, max 10)."
+                            }
+                        },
+                        "required": ["query"]
+                    }
+                }
+            }),
         ]
     }
 }
+
+impl<C: AiClient, M: AiClient, E: Embedder> ToolSet for Agent<'_, C, M, E> {
+    fn definitions(&self) -> Vec<serde_json::Value> {
+        self.tool_definitions()
+    }
+
+    fn dispatch(&mut self, tc: &ToolCall) -> Result<String> {
+        match tc.function.name.as_str() {
+            "run_capability" => self.handle_run_capability(tc),
+            "mutate_capability" => self.handle_mutate_capability(tc),
+            "search_capabilities" => self.handle_search_capabilities(tc),
+            other => anyhow::bail!("Unknown tool: {}", other),
+        }
+    }
+}
+
+impl<C: AiClient, M: AiClient, E: Embedder> LoopHooks for Agent<'_, C, M, E> {
+    type Output = String;
+
+    fn on_step(&mut self, step: usize) -> Result<()> {
+        println!("\n[AGENT STEP {}]", step + 1);
+        self.usage.check()?;
+        Ok(())
+    }
+
+    fn on_delta(&mut self, delta: StreamDelta<'_>) {
+        self.printer.on_delta(delta);
+    }
+
+    fn on_compaction(&mut self, compaction: &Compaction) {
+        println!(
+            "[CONTEXT] Compacted history: ~{} -> ~{} tokens ({} summarized, {} dropped)",
+            compaction.tokens_before,
+            compaction.tokens_after,
+            compaction.summarized,
+            compaction.dropped
+        );
+    }
+
+    fn on_response(&mut self, response: &ChatResponse) {
+        self.printer.finish();
+        self.usage.record(response);
+        if response
+            .choices
+            .first()
+            .is_some_and(|c| c.message.tool_calls.as_ref().is_some_and(|t| !t.is_empty()))
+        {
+            println!("[ASSISTANT TOOL CALLS]");
+        }
+    }
+
+    // No tool calls => final answer
+    fn on_text(
+        &mut self,
+        content: Option<String>,
+        _conversation: &mut Conversation,
+    ) -> Result<Control<String>> {
+        let content = content.unwrap_or_else(|| "<no content>".to_string());
+        println!("[AGENT FINAL]");
+        println!("{content}");
+        let task = std::mem::take(&mut self.task);
+        self.record_examples(&task);
+        Ok(Control::Stop(content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use se_runtime_core::ai_client::{ChatRequest, Message, ToolResult};
+    use se_runtime_core::capability_registry::CapabilityRegistry;
+    use se_runtime_core::index_document::IndexDocumentOptions;
+    use se_runtime_core::local_embedder::LocalEmbedder;
+    use se_runtime_core::registry_health::LoadMode;
+    use se_runtime_core::scripted_client::{text_response, tool_call_response, ScriptedClient};
+
+    use super::*;
+
+    fn last_tool_result(request: &ChatRequest) -> &ToolResult {
+        match request.messages.last() {
+            Some(Message::Tool(result)) => result,
+            other => panic!("expected a tool result, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_run_task_with_scripted_client() {
+        let root = std::env::temp_dir().join(format!("se_agent_{}", std::process::id()));
+        for (id, summary) in [
+            ("get_salary_details", "Returns employee salary details."),
+            ("get_car_details", "Returns company car details."),
+        ] {
+            let dir = root.join("crates").join(id);
+            fs::create_dir_all(&dir).unwrap();
+            let meta = json!({"id": id, "summary": summary, "binary": format!("{}.wasm", id)});
+            fs::write(dir.join("meta.json"), meta.to_string()).unwrap();
+        }
+        let root_str = root.to_str().unwrap();
+
+        let embedder = LocalEmbedder::new(256);
+        let mut store = CapabilityStore::load(
+            root_str,
+            Box::new(CapabilityRegistry::new(&root)),
+            LoadMode::Quarantine,
+            &embedder,
+            IndexDocumentOptions::default(),
+        )
+        .unwrap();
+        let runner = CapabilityRunner::new(&root).unwrap();
+
+        // Search, try to run (the binary isn't built, so it fails), answer.
+        let client = ScriptedClient::new(vec![
+            tool_call_response("call_1", "search_capabilities", json!({"query": "salary"})),
+            tool_call_response(
+                "call_2",
+                "run_capability",
+                json!({
+                    "capability_id": "get_salary_details",
+                    "input_json": "{\"employee_id\":\"EMP001\"}"
+                }),
+            ),
+            text_response("The salary lookup is unavailable."),
+        ]);
+        let mutation_client = ScriptedClient::new(Vec::new());
+
+        let mut agent = Agent::new(
+            &client,
+            &mutation_client,
+            &mut store,
+            &runner,
+            &embedder,
+            root_str,
+        );
+        let answer = agent
+            .run_task("What does EMP001 earn?", "", UsageMeter::default())
+            .unwrap();
+        assert_eq!(answer, "The salary lookup is unavailable.");
+
+        let requests = client.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(
+            requests[0].messages[1].content(),
+            Some("What does EMP001 earn?")
+        );
+        let search = last_tool_result(&requests[1]);
+        assert_eq!(search.tool_call_id, "call_1");
+        assert!(search.content.contains("id: get_salary_details"));
+        let run = last_tool_result(&requests[2]);
+        assert!(run
+            .content
+            .starts_with("ERROR: Capability 'get_salary_details' failed"));
+
+        assert_eq!(
+            store.run_stats("get_salary_details").unwrap().failure_count,
+            1
+        );
+        assert!(store.examples("get_salary_details").is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
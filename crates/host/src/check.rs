@@ -0,0 +1,54 @@
+// crates/host/src/check.rs
+
+//! Registry health check.
+//!
+//! Validates every capability's metadata (unreadable meta.json, id/directory
+//! mismatch, unknown status, missing binary, dangling `replaced_by`,
+//! mismatched embedding dimensions) and prints the report.
+//!
+//! Usage: `se_runtime_host check [--root DIR]`
+
+use anyhow::{Context, Result};
+
+use se_runtime_core::registry_backend::registry_from_env;
+use se_runtime_core::registry_health::{load_validated, LoadMode};
+
+/// Entry point for `se_runtime_host check ...`.
+pub fn run_cli(args: &[String]) -> Result<()> {
+    let mut root = "capabilities".to_string();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--root" => {
+                root = iter.next().context("--root needs a value")?.clone();
+            }
+            other => anyhow::bail!("unexpected argument: {}", other),
+        }
+    }
+
+    let registry = registry_from_env(&root)?;
+    let (records, report) = load_validated(registry.as_ref(), &root, LoadMode::Quarantine)?;
+
+    let errors = report.errors().count();
+    let warnings = report.warnings().count();
+    println!(
+        "[CHECK] {} capabilities read from {}, {} usable, {} errors, {} warnings",
+        report.loaded,
+        root,
+        records.len(),
+        errors,
+        warnings
+    );
+    for line in report.lines() {
+        println!("  {}", line);
+    }
+
+    if errors > 0 {
+        anyhow::bail!(
+            "{} capabilities would be quarantined",
+            report.quarantined_ids().len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,546 @@
+// crates/host/src/consolidate.rs
+
+//! Near-duplicate capability detection and consolidation.
+//!
+//! Active capabilities whose embeddings (and, where both have them, schemas)
+//! are very similar are grouped into clusters. Each cluster gets a keeper
+//! (best run history, then most recorded examples), and every other member
+//! is run next to it on the inputs recorded in the cluster's example logs.
+//! Members that behave identically on all of them are proposed as
+//! redundant; with `--apply` they're marked legacy with `replaced_by`
+//! pointing at the keeper.
+//!
+//! Comparison runs use a scratch copy of the database, so capabilities
+//! that write can't change real data.
+//!
+//! Usage: `se_runtime_host consolidate [--apply] [--report FILE] [--root DIR]`
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use se_runtime_core::capability_index::{cosine_similarity, CapabilityIndex};
+use se_runtime_core::capability_runner::CapabilityRunner;
+use se_runtime_core::embedding::embedder_from_env;
+use se_runtime_core::index_document::IndexDocumentOptions;
+use se_runtime_core::lifecycle::Lifecycle;
+use se_runtime_core::registry_backend::registry_from_env;
+use se_runtime_core::registry_health::{load_validated, LoadMode};
+use se_runtime_core::run_stats;
+use se_runtime_core::types::CapabilityRecord;
+
+use crate::examples::ExampleLog;
+
+const ACTOR: &str = "consolidate";
+
+/// Thresholds for treating two capabilities as near-duplicates.
+#[derive(Debug, Clone)]
+pub struct ConsolidateOptions {
+    /// Minimum cosine similarity of the main document embeddings.
+    pub min_similarity: f32,
+    /// Minimum overlap of schema property names, when both have schemas.
+    pub min_schema_overlap: f32,
+    /// Most shared inputs each cluster is run on.
+    pub max_inputs: usize,
+}
+
+impl Default for ConsolidateOptions {
+    fn default() -> Self {
+        Self {
+            min_similarity: 0.9,
+            min_schema_overlap: 0.5,
+            max_inputs: 5,
+        }
+    }
+}
+
+impl ConsolidateOptions {
+    /// Read options from environment variables.
+    ///
+    /// - CONSOLIDATE_MIN_SIMILARITY (default: 0.9)
+    /// - CONSOLIDATE_MIN_SCHEMA_OVERLAP (default: 0.5)
+    /// - CONSOLIDATE_MAX_INPUTS (default: 5)
+    pub fn from_env() -> Result<Self> {
+        let mut opts = Self::default();
+
+        if let Ok(v) = std::env::var("CONSOLIDATE_MIN_SIMILARITY") {
+            opts.min_similarity = v
+                .parse()
+                .with_context(|| format!("invalid CONSOLIDATE_MIN_SIMILARITY '{}'", v))?;
+        }
+        if let Ok(v) = std::env::var("CONSOLIDATE_MIN_SCHEMA_OVERLAP") {
+            opts.min_schema_overlap = v
+                .parse()
+                .with_context(|| format!("invalid CONSOLIDATE_MIN_SCHEMA_OVERLAP '{}'", v))?;
+        }
+        if let Ok(v) = std::env::var("CONSOLIDATE_MAX_INPUTS") {
+            opts.max_inputs = v
+                .parse()
+                .with_context(|| format!("invalid CONSOLIDATE_MAX_INPUTS '{}'", v))?;
+        }
+
+        Ok(opts)
+    }
+}
+
+/// Two capabilities that look like near-duplicates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimilarPair {
+    pub a: String,
+    pub b: String,
+    pub similarity: f32,
+    /// None when either capability has no schema.
+    pub schema_overlap: Option<f32>,
+}
+
+/// Input and output property names, prefixed by side.
+fn schema_fields(cap: &CapabilityRecord) -> Option<BTreeSet<String>> {
+    if cap.input_schema.is_none() && cap.output_schema.is_none() {
+        return None;
+    }
+
+    let mut fields = BTreeSet::new();
+    for (side, schema) in [("in", &cap.input_schema), ("out", &cap.output_schema)] {
+        if let Some(props) = schema
+            .as_ref()
+            .and_then(|s| s.get("properties"))
+            .and_then(Value::as_object)
+        {
+            fields.extend(props.keys().map(|k| format!("{}:{}", side, k)));
+        }
+    }
+    Some(fields)
+}
+
+/// Jaccard overlap of two capabilities' schema property names.
+pub fn schema_overlap(a: &CapabilityRecord, b: &CapabilityRecord) -> Option<f32> {
+    let (a, b) = (schema_fields(a)?, schema_fields(b)?);
+    let union = a.union(&b).count();
+    if union == 0 {
+        return Some(1.0);
+    }
+    Some(a.intersection(&b).count() as f32 / union as f32)
+}
+
+/// Pairs of capabilities above both thresholds, most similar first.
+///
+/// Capabilities without an embedding are skipped.
+pub fn similar_pairs(caps: &[CapabilityRecord], opts: &ConsolidateOptions) -> Vec<SimilarPair> {
+    let mut pairs = Vec::new();
+
+    for (i, a) in caps.iter().enumerate() {
+        for b in &caps[i + 1..] {
+            let (Some(ea), Some(eb)) = (&a.embedding, &b.embedding) else {
+                continue;
+            };
+            let similarity = cosine_similarity(ea, eb);
+            if similarity < opts.min_similarity {
+                continue;
+            }
+            let overlap = schema_overlap(a, b);
+            if overlap.is_some_and(|o| o < opts.min_schema_overlap) {
+                continue;
+            }
+            pairs.push(SimilarPair {
+                a: a.id.clone(),
+                b: b.id.clone(),
+                similarity,
+                schema_overlap: overlap,
+            });
+        }
+    }
+
+    pairs.sort_by(|x, y| {
+        y.similarity
+            .partial_cmp(&x.similarity)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    pairs
+}
+
+/// Group pairs into clusters of connected capabilities, each sorted by id.
+pub fn clusters(pairs: &[SimilarPair]) -> Vec<Vec<String>> {
+    let mut cluster_of: HashMap<&str, usize> = HashMap::new();
+    let mut members: Vec<BTreeSet<&str>> = Vec::new();
+
+    for pair in pairs {
+        match (
+            cluster_of.get(pair.a.as_str()).copied(),
+            cluster_of.get(pair.b.as_str()).copied(),
+        ) {
+            (Some(x), Some(y)) if x == y => {}
+            (Some(x), Some(y)) => {
+                let moved = std::mem::take(&mut members[y]);
+                for id in &moved {
+                    cluster_of.insert(id, x);
+                }
+                members[x].extend(moved);
+            }
+            (Some(x), None) => {
+                members[x].insert(&pair.b);
+                cluster_of.insert(&pair.b, x);
+            }
+            (None, Some(y)) => {
+                members[y].insert(&pair.a);
+                cluster_of.insert(&pair.a, y);
+            }
+            (None, None) => {
+                cluster_of.insert(&pair.a, members.len());
+                cluster_of.insert(&pair.b, members.len());
+                members.push(BTreeSet::from([pair.a.as_str(), pair.b.as_str()]));
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<String>> = members
+        .into_iter()
+        .filter(|m| !m.is_empty())
+        .map(|m| m.into_iter().map(String::from).collect())
+        .collect();
+    clusters.sort();
+    clusters
+}
+
+/// How a redundant candidate compared with its cluster's keeper.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// Same output on every shared input.
+    Equivalent { inputs: usize },
+    /// Outputs differed on some inputs.
+    Differs { agreed: usize, inputs: usize },
+    /// No shared inputs were recorded, or the keeper failed on all of them,
+    /// so behaviour couldn't be compared.
+    Untested,
+}
+
+/// Compare two capabilities' outcomes on the same inputs.
+///
+/// Outputs are compared as JSON when they parse; two failures count as
+/// agreement.
+pub fn compare(keeper: &[Result<String, String>], other: &[Result<String, String>]) -> Verdict {
+    if !keeper.iter().any(|k| k.is_ok()) {
+        return Verdict::Untested;
+    }
+
+    let normalise = |out: &str| {
+        serde_json::from_str::<Value>(out.trim()).unwrap_or_else(|_| Value::String(out.into()))
+    };
+    let agreed = keeper
+        .iter()
+        .zip(other)
+        .filter(|(k, o)| match (k, o) {
+            (Ok(k), Ok(o)) => normalise(k) == normalise(o),
+            (Err(_), Err(_)) => true,
+            _ => false,
+        })
+        .count();
+
+    if agreed == keeper.len() {
+        Verdict::Equivalent {
+            inputs: keeper.len(),
+        }
+    } else {
+        Verdict::Differs {
+            agreed,
+            inputs: keeper.len(),
+        }
+    }
+}
+
+/// A proposed merge of one capability into another.
+#[derive(Debug, Clone)]
+pub struct Proposal {
+    pub redundant: String,
+    pub keeper: String,
+    pub similarity: f32,
+    pub verdict: Verdict,
+}
+
+impl Proposal {
+    /// Only capabilities shown to behave the same are merged automatically.
+    pub fn is_safe(&self) -> bool {
+        matches!(self.verdict, Verdict::Equivalent { .. })
+    }
+}
+
+/// Runs capabilities against a scratch copy of the database.
+struct Sandbox {
+    runner: CapabilityRunner,
+    source_db: PathBuf,
+    scratch_db: PathBuf,
+}
+
+impl Sandbox {
+    fn new(capabilities_root: &str) -> Result<Self> {
+        let source_db = CapabilityRunner::new(capabilities_root)?
+            .db_path()
+            .to_path_buf();
+        let scratch_db =
+            std::env::temp_dir().join(format!("se_consolidate_{}.db", std::process::id()));
+        let runner = CapabilityRunner::with_db_path(capabilities_root, &scratch_db)?;
+
+        Ok(Self {
+            runner,
+            source_db,
+            scratch_db,
+        })
+    }
+
+    /// Run on a fresh copy so every run starts from the same data.
+    fn run(&self, cap: &CapabilityRecord, input_json: &str) -> Result<String, String> {
+        if self.source_db.exists() {
+            fs::copy(&self.source_db, &self.scratch_db).map_err(|e| e.to_string())?;
+        }
+        self.runner
+            .run_capability(cap, input_json)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.scratch_db);
+    }
+}
+
+/// Entry point for `se_runtime_host consolidate ...`.
+pub fn run_cli(args: &[String]) -> Result<()> {
+    let mut root = "capabilities".to_string();
+    let mut apply = false;
+    let mut report_path = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--root" => {
+                root = iter.next().context("--root needs a value")?.clone();
+            }
+            "--report" => {
+                report_path = Some(iter.next().context("--report needs a value")?.clone());
+            }
+            "--apply" => apply = true,
+            other => anyhow::bail!("unexpected argument: {}", other),
+        }
+    }
+
+    let opts = ConsolidateOptions::from_env()?;
+    let embedder = embedder_from_env()?;
+    let registry = registry_from_env(&root)?;
+    let (records, _) = load_validated(registry.as_ref(), &root, LoadMode::Quarantine)?;
+
+    let mut active: Vec<CapabilityRecord> = records.into_iter().filter(|r| r.is_active()).collect();
+    CapabilityIndex::build_cached(
+        &mut active,
+        &embedder,
+        &IndexDocumentOptions::from_env()?,
+        registry.as_ref(),
+    )?;
+
+    let pairs = similar_pairs(&active, &opts);
+    let clusters = clusters(&pairs);
+    println!(
+        "[CONSOLIDATE] {} near-duplicate pairs in {} clusters among {} active capabilities",
+        pairs.len(),
+        clusters.len(),
+        active.len()
+    );
+
+    let by_id: HashMap<&str, &CapabilityRecord> =
+        active.iter().map(|r| (r.id.as_str(), r)).collect();
+    let stats = registry.run_stats()?;
+    let example_log = ExampleLog::new(&root);
+    let sandbox = Sandbox::new(&root)?;
+
+    let mut proposals = Vec::new();
+    for cluster in &clusters {
+        let mut examples = BTreeMap::new();
+        for id in cluster {
+            examples.insert(id.as_str(), example_log.load(id)?);
+        }
+
+        let keeper = cluster
+            .iter()
+            .max_by(|a, b| {
+                let key = |id: &String| (run_stats::prior(&stats, id), examples[id.as_str()].len());
+                key(a)
+                    .partial_cmp(&key(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| b.cmp(a))
+            })
+            .context("empty cluster")?;
+
+        let mut inputs: Vec<&str> = Vec::new();
+        for example in examples.values().flatten() {
+            if inputs.len() < opts.max_inputs && !inputs.contains(&example.input_json.as_str()) {
+                inputs.push(&example.input_json);
+            }
+        }
+
+        let outcomes = |id: &str| -> Vec<Result<String, String>> {
+            inputs.iter().map(|i| sandbox.run(by_id[id], i)).collect()
+        };
+        let keeper_outcomes = outcomes(keeper);
+
+        for id in cluster.iter().filter(|id| *id != keeper) {
+            let similarity = pairs
+                .iter()
+                .filter(|p| (p.a == *id && p.b == *keeper) || (p.b == *id && p.a == *keeper))
+                .map(|p| p.similarity)
+                .next()
+                .unwrap_or_else(|| {
+                    match (
+                        &by_id[id.as_str()].embedding,
+                        &by_id[keeper.as_str()].embedding,
+                    ) {
+                        (Some(a), Some(b)) => cosine_similarity(a, b),
+                        _ => 0.0,
+                    }
+                });
+
+            proposals.push(Proposal {
+                redundant: id.clone(),
+                keeper: keeper.clone(),
+                similarity,
+                verdict: compare(&keeper_outcomes, &outcomes(id)),
+            });
+        }
+    }
+
+    let report = render_report(&pairs, &proposals, apply);
+    println!("{}", report);
+    if let Some(path) = &report_path {
+        fs::write(path, &report).with_context(|| format!("failed to write {}", path))?;
+        println!("[CONSOLIDATE] Report written to {}", path);
+    }
+
+    if apply {
+        let lifecycle = Lifecycle::new(registry.as_ref());
+        for proposal in proposals.iter().filter(|p| p.is_safe()) {
+            lifecycle.supersede(
+                &proposal.redundant,
+                &proposal.keeper,
+                ACTOR,
+                &format!(
+                    "near-duplicate of {} (similarity {:.3}, same output on shared inputs)",
+                    proposal.keeper, proposal.similarity
+                ),
+            )?;
+            println!(
+                "[CONSOLIDATE] Marked '{}' legacy, replaced by '{}'",
+                proposal.redundant, proposal.keeper
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Markdown report of the pairs found and what was (or would be) merged.
+fn render_report(pairs: &[SimilarPair], proposals: &[Proposal], applied: bool) -> String {
+    let mut out = String::from("# Capability consolidation report\n\n## Near-duplicate pairs\n\n");
+
+    if pairs.is_empty() {
+        out.push_str("None found.\n");
+        return out;
+    }
+
+    out.push_str("| A | B | Similarity | Schema overlap |\n|---|---|---|---|\n");
+    for p in pairs {
+        let overlap = p
+            .schema_overlap
+            .map(|o| format!("{:.2}", o))
+            .unwrap_or_else(|| "-".to_string());
+        out.push_str(&format!(
+            "| {} | {} | {:.3} | {} |\n",
+            p.a, p.b, p.similarity, overlap
+        ));
+    }
+
+    out.push_str("\n## Proposals\n\n");
+    for p in proposals {
+        let (verdict, action) = match &p.verdict {
+            Verdict::Equivalent { inputs } => (
+                format!("same output on {} shared inputs", inputs),
+                if applied { "merged" } else { "merge" },
+            ),
+            Verdict::Differs { agreed, inputs } => (
+                format!("agreed on {} of {} shared inputs", agreed, inputs),
+                "review",
+            ),
+            Verdict::Untested => ("no successful shared inputs".to_string(), "review"),
+        };
+        out.push_str(&format!(
+            "- **{}**: `{}` into `{}` (similarity {:.3}; {})\n",
+            action, p.redundant, p.keeper, p.similarity, verdict
+        ));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(id: &str, embedding: Vec<f32>, input_fields: &[&str]) -> CapabilityRecord {
+        let props: serde_json::Map<String, Value> = input_fields
+            .iter()
+            .map(|f| (f.to_string(), json!({"type": "string"})))
+            .collect();
+        CapabilityRecord {
+            embedding: Some(embedding),
+            input_schema: Some(json!({"type": "object", "properties": props})),
+            ..CapabilityRecord::new(id, id)
+        }
+    }
+
+    #[test]
+    fn test_pairs_need_similar_embeddings_and_schemas() {
+        let caps = vec![
+            record("get_salary_details", vec![1.0, 0.0, 0.0], &["employee_id"]),
+            record("get_salary_info", vec![0.99, 0.1, 0.0], &["employee_id"]),
+            record("get_pay", vec![0.98, 0.15, 0.0], &["employee_id"]),
+            // Close embedding, different inputs.
+            record(
+                "get_salary_band",
+                vec![0.99, 0.05, 0.0],
+                &["grade", "region"],
+            ),
+            record("get_car_details", vec![0.0, 0.0, 1.0], &["employee_id"]),
+        ];
+
+        let pairs = similar_pairs(&caps, &ConsolidateOptions::default());
+        assert!(pairs.iter().all(|p| p.schema_overlap == Some(1.0)));
+        assert_eq!(
+            clusters(&pairs),
+            vec![vec!["get_pay", "get_salary_details", "get_salary_info"]]
+        );
+    }
+
+    #[test]
+    fn test_compare_outputs_as_json() {
+        let ok = |s: &str| Ok(s.to_string());
+        let keeper = vec![ok(r#"{"a": 1, "b": 2}"#), Err("boom".to_string())];
+
+        assert_eq!(
+            compare(&keeper, &[ok(r#"{"b":2,"a":1}"#), Err("bang".to_string())]),
+            Verdict::Equivalent { inputs: 2 }
+        );
+        assert_eq!(
+            compare(&keeper, &[ok(r#"{"a": 1}"#), Err("bang".to_string())]),
+            Verdict::Differs {
+                agreed: 1,
+                inputs: 2
+            }
+        );
+        assert_eq!(compare(&[], &[]), Verdict::Untested);
+        assert_eq!(
+            compare(&keeper[1..], &[Err("bang".to_string())]),
+            Verdict::Untested
+        );
+    }
+}
@@ -0,0 +1,280 @@
+// crates/host/src/eval.rs
+
+//! Retrieval evaluation over a labelled task set.
+//!
+//! Reads a JSONL file of `{"task": ..., "expected_capability_ids": [...]}`
+//! records, ranks capabilities for each task the same way the runtime does,
+//! and reports recall@k, MRR and the tasks that missed.
+//!
+//! Usage: `se_runtime_host eval <tasks.jsonl> [--k N] [--root DIR]`
+
+use std::fs;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use se_runtime_core::capability_index::RetrievalOptions;
+use se_runtime_core::embedding::{embedder_from_env, Embedder};
+use se_runtime_core::index_document::IndexDocumentOptions;
+use se_runtime_core::registry_backend::registry_from_env;
+use se_runtime_core::registry_health::LoadMode;
+
+use crate::store::CapabilityStore;
+
+/// One labelled task.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvalCase {
+    pub task: String,
+    pub expected_capability_ids: Vec<String>,
+}
+
+/// Outcome of ranking a single task.
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub case: EvalCase,
+    /// Full ranking of active capabilities, best first.
+    pub ranking: Vec<(String, f32)>,
+}
+
+impl CaseResult {
+    /// 1-based rank of the first expected capability, if it was ranked at all.
+    pub fn first_hit_rank(&self) -> Option<usize> {
+        self.ranking
+            .iter()
+            .position(|(id, _)| self.case.expected_capability_ids.contains(id))
+            .map(|p| p + 1)
+    }
+
+    /// Fraction of expected capabilities found in the top-k.
+    pub fn recall_at(&self, k: usize) -> f32 {
+        if self.case.expected_capability_ids.is_empty() {
+            return 1.0;
+        }
+        let top: Vec<&String> = self.ranking.iter().take(k).map(|(id, _)| id).collect();
+        let found = self
+            .case
+            .expected_capability_ids
+            .iter()
+            .filter(|id| top.contains(id))
+            .count();
+        found as f32 / self.case.expected_capability_ids.len() as f32
+    }
+
+    /// Reciprocal rank of the first expected capability (0 if never ranked).
+    pub fn reciprocal_rank(&self) -> f32 {
+        self.first_hit_rank().map(|r| 1.0 / r as f32).unwrap_or(0.0)
+    }
+}
+
+/// Aggregate metrics over a task set.
+#[derive(Debug, Clone)]
+pub struct EvalReport {
+    pub k: usize,
+    pub results: Vec<CaseResult>,
+}
+
+impl EvalReport {
+    /// Mean recall@k over all tasks.
+    pub fn recall_at_k(&self) -> f32 {
+        mean(self.results.iter().map(|r| r.recall_at(self.k)))
+    }
+
+    /// Mean reciprocal rank over all tasks.
+    pub fn mrr(&self) -> f32 {
+        mean(self.results.iter().map(|r| r.reciprocal_rank()))
+    }
+
+    /// Tasks where at least one expected capability is outside the top-k.
+    pub fn misses(&self) -> Vec<&CaseResult> {
+        self.results
+            .iter()
+            .filter(|r| r.recall_at(self.k) < 1.0)
+            .collect()
+    }
+
+    /// Print the report to stdout.
+    pub fn print(&self, label: &str) {
+        println!(
+            "\n[EVAL] {} tasks, k = {}, {}",
+            self.results.len(),
+            self.k,
+            label
+        );
+        println!("  recall@{}: {:.3}", self.k, self.recall_at_k());
+        println!("  MRR:       {:.3}", self.mrr());
+
+        let misses = self.misses();
+        if misses.is_empty() {
+            println!("\nNo misses.");
+            return;
+        }
+
+        println!("\nMisses ({}):", misses.len());
+        for miss in misses {
+            println!("  - task: {}", miss.case.task);
+            println!(
+                "    expected: {}",
+                miss.case.expected_capability_ids.join(", ")
+            );
+            let top: Vec<String> = miss
+                .ranking
+                .iter()
+                .take(self.k)
+                .map(|(id, score)| format!("{id} ({score:.3})"))
+                .collect();
+            println!("    got:      {}", top.join(", "));
+            for expected in &miss.case.expected_capability_ids {
+                match miss.ranking.iter().position(|(id, _)| id == expected) {
+                    Some(p) => println!("    rank of {}: {}", expected, p + 1),
+                    None => println!("    rank of {}: not ranked", expected),
+                }
+            }
+        }
+    }
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, n) = values.fold((0.0, 0usize), |(s, n), v| (s + v, n + 1));
+    if n == 0 {
+        0.0
+    } else {
+        sum / n as f32
+    }
+}
+
+/// Parse a JSONL task set, skipping blank lines.
+pub fn load_cases(path: &str) -> Result<Vec<EvalCase>> {
+    let data = fs::read_to_string(path).with_context(|| format!("failed to read {}", path))?;
+
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("failed to parse {} line {}", path, i + 1))
+        })
+        .collect()
+}
+
+/// Rank every case against the store.
+pub fn evaluate(
+    store: &CapabilityStore,
+    embedder: &impl Embedder,
+    cases: Vec<EvalCase>,
+    k: usize,
+) -> Result<EvalReport> {
+    let mut results = Vec::with_capacity(cases.len());
+    for case in cases {
+        let ranking = store.rank_for_task(&case.task, embedder, store.len())?;
+        results.push(CaseResult { case, ranking });
+    }
+    Ok(EvalReport { k, results })
+}
+
+/// Entry point for `se_runtime_host eval ...`.
+pub fn run_cli(args: &[String]) -> Result<()> {
+    let mut path: Option<&str> = None;
+    let mut k = 3usize;
+    let mut root = "capabilities".to_string();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--k" => {
+                let v = iter.next().context("--k needs a value")?;
+                k = v
+                    .parse()
+                    .with_context(|| format!("invalid --k value: {}", v))?;
+            }
+            "--root" => {
+                root = iter.next().context("--root needs a value")?.clone();
+            }
+            other if path.is_none() => path = Some(other),
+            other => anyhow::bail!("unexpected argument: {}", other),
+        }
+    }
+
+    let path = path.context("usage: se_runtime_host eval <tasks.jsonl> [--k N] [--root DIR]")?;
+    if k == 0 {
+        anyhow::bail!("--k must be at least 1");
+    }
+
+    let cases = load_cases(path)?;
+    if cases.is_empty() {
+        anyhow::bail!("no tasks found in {}", path);
+    }
+
+    let embedder = embedder_from_env()?;
+    let document = IndexDocumentOptions::from_env()?;
+    let label = format!(
+        "embedder = {}, document = {:?}{}, root = {}",
+        std::env::var("EMBEDDING_PROVIDER").unwrap_or_else(|_| "foundry".to_string()),
+        document.fields,
+        if document.multi_vector {
+            " (multi-vector)"
+        } else {
+            ""
+        },
+        root
+    );
+    let mut store = CapabilityStore::load(
+        &root,
+        registry_from_env(&root)?,
+        LoadMode::from_env()?,
+        &embedder,
+        document,
+    )?;
+    // Ignore local run history unless asked for, so results are comparable
+    // across machines.
+    if std::env::var_os("RETRIEVAL_USAGE_WEIGHT").is_some() {
+        store.set_usage_weight(RetrievalOptions::from_env()?.usage_weight);
+    } else {
+        store.set_usage_weight(0.0);
+    }
+
+    let report = evaluate(&store, &embedder, cases, k)?;
+    report.print(&label);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(expected: &[&str], ranking: &[&str]) -> CaseResult {
+        CaseResult {
+            case: EvalCase {
+                task: "task".to_string(),
+                expected_capability_ids: expected.iter().map(|s| s.to_string()).collect(),
+            },
+            ranking: ranking.iter().map(|id| (id.to_string(), 0.0)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_recall_and_mrr() {
+        let report = EvalReport {
+            k: 2,
+            results: vec![
+                result(&["a"], &["a", "b", "c"]),
+                result(&["c"], &["a", "b", "c"]),
+                result(&["b", "d"], &["b", "c", "d"]),
+            ],
+        };
+
+        // recall@2: 1.0, 0.0, 0.5
+        assert!((report.recall_at_k() - 0.5).abs() < 1e-6);
+        // reciprocal ranks: 1, 1/3, 1
+        assert!((report.mrr() - (1.0 + 1.0 / 3.0 + 1.0) / 3.0).abs() < 1e-6);
+        assert_eq!(report.misses().len(), 2);
+    }
+
+    #[test]
+    fn test_unranked_expected_scores_zero() {
+        let r = result(&["missing"], &["a", "b"]);
+        assert_eq!(r.first_hit_rank(), None);
+        assert_eq!(r.reciprocal_rank(), 0.0);
+        assert_eq!(r.recall_at(2), 0.0);
+    }
+}
@@ -0,0 +1,185 @@
+// crates/host/src/examples.rs
+
+//! Per-capability log of tasks the capability helped solve.
+//!
+//! Each capability keeps `examples.jsonl` in its crate directory with one
+//! `{task, input_json, capability_id}` record per line. Recent tasks are
+//! merged into the capability's `example_tasks` so they're indexed, and a
+//! few are shown to the agent as few-shot examples of how to call it.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// File name of the examples log inside a capability's crate directory.
+pub const EXAMPLES_FILE: &str = "examples.jsonl";
+
+/// One task solved by running a capability with a given input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapabilityExample {
+    pub task: String,
+    pub input_json: String,
+    pub capability_id: String,
+    /// RFC 3339 timestamp of when the example was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recorded_at: Option<String>,
+}
+
+/// Reads and appends the examples logs under a capabilities root.
+pub struct ExampleLog {
+    crates_dir: PathBuf,
+}
+
+impl ExampleLog {
+    pub fn new(capabilities_root: &str) -> Self {
+        Self {
+            crates_dir: Path::new(capabilities_root).join("crates"),
+        }
+    }
+
+    fn path(&self, capability_id: &str) -> PathBuf {
+        self.crates_dir.join(capability_id).join(EXAMPLES_FILE)
+    }
+
+    /// All recorded examples for a capability, oldest first (empty if none).
+    ///
+    /// Lines that don't parse (e.g. half-written by a crash during `append`)
+    /// are skipped with a warning rather than failing the whole log.
+    pub fn load(&self, capability_id: &str) -> Result<Vec<CapabilityExample>> {
+        let path = self.path(capability_id);
+
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+
+        let examples = data
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .filter_map(|(i, line)| match serde_json::from_str(line) {
+                Ok(example) => Some(example),
+                Err(e) => {
+                    println!(
+                        "[EXAMPLES] Warning: skipping {} line {}: {}",
+                        path.display(),
+                        i + 1,
+                        e
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        Ok(examples)
+    }
+
+    /// Append an example unless the same task and input are already logged.
+    ///
+    /// Returns whether it was added.
+    pub fn append(&self, example: &CapabilityExample) -> Result<bool> {
+        let existing = self.load(&example.capability_id)?;
+        if existing
+            .iter()
+            .any(|e| e.task == example.task && e.input_json == example.input_json)
+        {
+            return Ok(false);
+        }
+
+        let path = self.path(&example.capability_id);
+        // Start a fresh line after a torn write so this record stays intact.
+        let torn = fs::read(&path).is_ok_and(|data| data.last().is_some_and(|b| *b != b'\n'));
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        if torn {
+            writeln!(file).with_context(|| format!("failed to write {}", path.display()))?;
+        }
+        writeln!(file, "{}", serde_json::to_string(example)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+
+        Ok(true)
+    }
+}
+
+/// Up to `n` distinct task texts, most recent first.
+pub fn recent_tasks(examples: &[CapabilityExample], n: usize) -> Vec<String> {
+    let mut tasks: Vec<String> = Vec::new();
+    for example in examples.iter().rev() {
+        if tasks.len() == n {
+            break;
+        }
+        if !tasks.contains(&example.task) {
+            tasks.push(example.task.clone());
+        }
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(task: &str, input: &str) -> CapabilityExample {
+        CapabilityExample {
+            task: task.to_string(),
+            input_json: input.to_string(),
+            capability_id: "get_salary_details".to_string(),
+            recorded_at: None,
+        }
+    }
+
+    #[test]
+    fn test_append_skips_duplicates_and_round_trips() {
+        let root = std::env::temp_dir().join(format!("se_examples_{}", std::process::id()));
+        fs::create_dir_all(root.join("crates").join("get_salary_details")).unwrap();
+        let log = ExampleLog::new(root.to_str().unwrap());
+
+        let first = example("Salary of EMP001?", r#"{"employee_id":"EMP001"}"#);
+        assert!(log.append(&first).unwrap());
+        assert!(!log.append(&first).unwrap());
+        assert!(log
+            .append(&example("Salary of EMP002?", r#"{"employee_id":"EMP002"}"#))
+            .unwrap());
+
+        let loaded = log.load("get_salary_details").unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0], first);
+        assert!(log.load("unknown").unwrap().is_empty());
+
+        // A torn last line is skipped, and appending still works.
+        let path = root
+            .join("crates")
+            .join("get_salary_details")
+            .join(EXAMPLES_FILE);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, r#"{{"task": "Salary of EMP0"#).unwrap();
+        assert_eq!(log.load("get_salary_details").unwrap().len(), 2);
+        assert!(log
+            .append(&example("Salary of EMP003?", r#"{"employee_id":"EMP003"}"#))
+            .unwrap());
+        assert_eq!(log.load("get_salary_details").unwrap().len(), 3);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_recent_tasks_are_distinct_newest_first() {
+        let examples = vec![
+            example("a", "1"),
+            example("b", "2"),
+            example("a", "3"),
+            example("c", "4"),
+        ];
+        assert_eq!(recent_tasks(&examples, 2), vec!["c", "a"]);
+        assert_eq!(recent_tasks(&examples, 5), vec!["c", "a", "b"]);
+    }
+}
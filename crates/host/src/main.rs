@@ -1,35 +1,80 @@
 mod agent;
+mod check;
+mod consolidate;
+mod eval;
+mod examples;
 mod mutation_agent;
+mod prune;
+mod reranker;
+mod status;
 mod store;
+mod stream_printer;
 
 use std::io::{self, BufRead, Write};
 
 use anyhow::Result;
 
+use se_runtime_core::ai_client::{chat_client_from_env, ChatRole};
+use se_runtime_core::capability_index::RetrievalOptions;
 use se_runtime_core::capability_runner::CapabilityRunner;
-use se_runtime_core::embedding::MicrosoftFoundryEmbedder;
-use se_runtime_core::foundry_client::FoundryClient;
+use se_runtime_core::embedding::embedder_from_env;
+use se_runtime_core::index_document::IndexDocumentOptions;
+use se_runtime_core::registry_backend::registry_from_env;
+use se_runtime_core::registry_health::LoadMode;
+use se_runtime_core::usage::{BudgetExceeded, UsageMeter};
 
 use agent::Agent;
+use reranker::{LlmReranker, Reranker};
 use store::CapabilityStore;
 
 fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        None => run_interactive(),
+        Some("eval") => eval::run_cli(&args[1..]),
+        Some("check") => check::run_cli(&args[1..]),
+        Some("consolidate") => consolidate::run_cli(&args[1..]),
+        Some("prune") => prune::run_cli(&args[1..]),
+        Some("revive") => status::run_revive(&args[1..]),
+        Some("retire") => status::run_retire(&args[1..]),
+        Some("history") => status::run_history(&args[1..]),
+        Some(other) => anyhow::bail!(
+            "unknown command '{}'\n\nUsage:\n  se_runtime_host                 interactive agent\n  se_runtime_host eval <tasks.jsonl> [--k N] [--root DIR]\n  se_runtime_host check [--root DIR]\n  se_runtime_host prune [--dry-run] [--root DIR]\n  se_runtime_host consolidate [--apply] [--report FILE] [--root DIR]\n  se_runtime_host revive <id> [--reason TEXT] [--force] [--root DIR]\n  se_runtime_host retire <id> [--reason TEXT] [--root DIR]\n  se_runtime_host history <id> [--root DIR]",
+            other
+        ),
+    }
+}
+
+/// Interactive task loop.
+fn run_interactive() -> Result<()> {
     let capabilities_root = "capabilities";
 
     // Initialise services.
-    let embedder = MicrosoftFoundryEmbedder::from_env()?;
-    let ai_client = FoundryClient::from_env()?;
+    // EMBEDDING_PROVIDER=local gives fully offline retrieval.
+    let embedder = embedder_from_env()?;
+    // CHAT_PROVIDER picks Foundry or any OpenAI-compatible server.
+    let ai_client = chat_client_from_env(ChatRole::Agent)?;
 
-    // Mutation agent can use a different (coding-focused) model.
-    // Falls back to FOUNDRY_CHAT_DEPLOYMENT if FOUNDRY_MUTATION_DEPLOYMENT is not set.
-    let mutation_client =
-        FoundryClient::from_env_with_deployment_var("FOUNDRY_MUTATION_DEPLOYMENT")
-            .or_else(|_| FoundryClient::from_env())?;
+    // Mutation agent can use a different (coding-focused) model or provider.
+    // See `chat_client_from_env` for the fallbacks.
+    let mutation_client = chat_client_from_env(ChatRole::Mutation)?;
 
     let runner = CapabilityRunner::new(capabilities_root)?;
+    let retrieval_opts = RetrievalOptions::from_env()?;
+    let reranker = LlmReranker::from_env(&ai_client)?;
+    // Each task starts from this; reranking is charged to the task's budget.
+    let task_usage = UsageMeter::from_env(ChatRole::Agent)?;
 
     // Load capability store (state).
-    let mut store = CapabilityStore::load(capabilities_root, &embedder)?;
+    let mut store = CapabilityStore::load(
+        capabilities_root,
+        registry_from_env(capabilities_root)?,
+        LoadMode::from_env()?,
+        &embedder,
+        IndexDocumentOptions::from_env()?,
+    )?;
+    store.set_usage_weight(retrieval_opts.usage_weight);
     println!("Loaded {} capabilities from registry.", store.len());
     println!("\nSelf-Evolving Agent Runtime");
     println!("Type your task and press Enter. Type 'quit' or 'exit' to stop.\n");
@@ -57,9 +102,20 @@ fn main() -> Result<()> {
         }
 
         // Find relevant capabilities for this task
-        let (caps_summary, nearest) = store.capabilities_summary_for_task(task, &embedder, 2)?;
-        println!("\nNearest capabilities:");
-        for (id, score) in &nearest {
+        let mut usage = task_usage.clone();
+        let (caps_summary, retrieval) = store.capabilities_summary_for_task(
+            task,
+            &embedder,
+            &retrieval_opts,
+            reranker.as_ref().map(|r| r as &dyn Reranker),
+            &mut usage,
+        )?;
+        if retrieval.is_no_match() {
+            println!("\nNo capability is a good match. Closest:");
+        } else {
+            println!("\nNearest capabilities:");
+        }
+        for (id, score) in retrieval.matches.iter().chain(&retrieval.closest) {
             println!("  - {id} (score = {score:.3})");
         }
 
@@ -72,11 +128,14 @@ fn main() -> Result<()> {
             &embedder,
             capabilities_root,
         );
-        match agent.run_task(task, &caps_summary) {
+        match agent.run_task(task, &caps_summary, usage) {
             Ok(answer) => {
                 println!("\n[FINAL ANSWER]");
                 println!("{answer}\n");
             }
+            Err(e) if e.downcast_ref::<BudgetExceeded>().is_some() => {
+                println!("\n[BUDGET] Task stopped: {e}\n");
+            }
             Err(e) => {
                 println!("\n[ERROR] {e}\n");
             }
@@ -1,21 +1,32 @@
 // crates/host/src/mutation_agent/capability_ops.rs
 
-//! Capability filesystem operations: copying, updating metadata, etc.
+//! Capability operations: copying crates on disk, registering metadata, etc.
 
 use std::fs;
 use std::path::Path;
 
-use anyhow::Result;
-use serde_json::json;
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use se_runtime_core::capability_registry::HISTORY_FILE;
+use se_runtime_core::lifecycle::Lifecycle;
+use se_runtime_core::registry_backend::RegistryBackend;
+use se_runtime_core::types::CapabilityRecord;
+
+use crate::examples::EXAMPLES_FILE;
 
 /// Handles capability filesystem operations.
 pub struct CapabilityOps<'a> {
     capabilities_root: &'a str,
+    registry: &'a dyn RegistryBackend,
 }
 
 impl<'a> CapabilityOps<'a> {
-    pub fn new(capabilities_root: &'a str) -> Self {
-        Self { capabilities_root }
+    pub fn new(capabilities_root: &'a str, registry: &'a dyn RegistryBackend) -> Self {
+        Self {
+            capabilities_root,
+            registry,
+        }
     }
 
     /// Create a new capability by copying the parent's entire crate directory.
@@ -39,6 +50,16 @@ impl<'a> CapabilityOps<'a> {
         // Copy entire directory tree
         self.copy_dir_recursive(&src, &dst)?;
 
+        // The parent's metadata, examples and history don't belong to the new
+        // capability. Its metadata is written by the registry on `register`
+        // (meta.json for the fs backend), so until then it isn't loaded.
+        for log in ["meta.json", EXAMPLES_FILE, HISTORY_FILE] {
+            let path = dst.join(log);
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+
         // Update package name in Cargo.toml
         let cargo_path = dst.join("Cargo.toml");
         let cargo_content = fs::read_to_string(&cargo_path)?;
@@ -48,14 +69,18 @@ impl<'a> CapabilityOps<'a> {
         );
         fs::write(&cargo_path, updated_cargo)?;
 
-        // Update meta.json with new id (pointing to WASM file)
-        let meta = json!({
-            "id": new_id,
-            "summary": "New capability (pending implementation)",
-            "binary": format!("../../target/wasm32-wasip1/release/{}.wasm", new_id)
-        });
-        fs::write(dst.join("meta.json"), serde_json::to_string_pretty(&meta)?)?;
+        Ok(())
+    }
 
+    /// Delete an unregistered capability's crate directory.
+    pub fn discard(&self, capability_id: &str) -> Result<()> {
+        let dir = Path::new(self.capabilities_root)
+            .join("crates")
+            .join(capability_id);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("failed to remove {}", dir.display()))?;
+        }
         Ok(())
     }
 
@@ -78,42 +103,39 @@ impl<'a> CapabilityOps<'a> {
         Ok(())
     }
 
-    /// Update the capability's meta.json with a new summary.
-    pub fn update_meta_json(&self, capability_id: &str, summary: &str) -> Result<()> {
-        let meta_path = Path::new(self.capabilities_root)
-            .join("crates")
-            .join(capability_id)
-            .join("meta.json");
-
-        let meta = json!({
-            "id": capability_id,
-            "summary": summary,
-            "binary": format!("../../target/wasm32-wasip1/release/{}.wasm", capability_id),
-            "status": "active"
-        });
-
-        fs::write(&meta_path, serde_json::to_string_pretty(&meta)?)?;
-        Ok(())
+    /// Register the capability in the registry with its final summary,
+    /// optional schemas and tags.
+    pub fn register(
+        &self,
+        capability_id: &str,
+        summary: &str,
+        input_schema: Option<&Value>,
+        output_schema: Option<&Value>,
+        tags: &[String],
+    ) -> Result<()> {
+        let record = CapabilityRecord {
+            binary: Some(format!(
+                "../../target/wasm32-wasip1/release/{}.wasm",
+                capability_id
+            )),
+            input_schema: input_schema.cloned(),
+            output_schema: output_schema.cloned(),
+            tags: tags.to_vec(),
+            ..CapabilityRecord::new(capability_id, summary)
+        };
+
+        self.registry.upsert(&record)
     }
 
     /// Mark a capability as legacy (replaced by a newer version).
     pub fn mark_as_legacy(&self, capability_id: &str, replaced_by: &str) -> Result<()> {
-        let meta_path = Path::new(self.capabilities_root)
-            .join("crates")
-            .join(capability_id)
-            .join("meta.json");
-
-        if !meta_path.exists() {
-            anyhow::bail!("Capability '{}' not found", capability_id);
-        }
-
-        let content = fs::read_to_string(&meta_path)?;
-        let mut meta: serde_json::Value = serde_json::from_str(&content)?;
-
-        meta["status"] = json!("legacy");
-        meta["replaced_by"] = json!(replaced_by);
+        Lifecycle::new(self.registry).supersede(
+            capability_id,
+            replaced_by,
+            "mutation_agent",
+            "replaced by a mutation",
+        )?;
 
-        fs::write(&meta_path, serde_json::to_string_pretty(&meta)?)?;
         println!(
             "[MUTATION] Marked '{}' as legacy (replaced by '{}')",
             capability_id, replaced_by
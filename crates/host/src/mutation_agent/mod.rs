@@ -15,20 +15,58 @@ mod tools;
 use std::path::Path;
 
 use anyhow::{Context, Result};
-use serde_json::json;
 
-use se_runtime_core::ai_client::{AiClient, ChatRequest};
+use se_runtime_core::ai_client::{
+    AiClient, ChatRequest, ChatResponse, ChatRole, GenerationParams, Message, ResponseFormat,
+    ToolCall,
+};
+use se_runtime_core::conversation::{Compaction, ContextPolicy, Conversation, Keep, OutputLimit};
+use se_runtime_core::registry_backend::RegistryBackend;
+use se_runtime_core::streaming::StreamDelta;
+use se_runtime_core::tool_loop::{Control, LoopHooks, ToolLoop, ToolSet};
+use se_runtime_core::usage::{UsageMeter, UsageTotals};
+
+use crate::stream_printer::StreamPrinter;
 
 use capability_ops::CapabilityOps;
 use prompts::build_system_prompt;
 use tools::{CompletionArgs, ToolHandler, TOOL_DEFINITIONS};
 
+/// Default generation settings, overridable with MUTATION_* variables.
+///
+/// A `write_file` call carries a whole main.rs, so leave room for it; and
+/// each write/build/test step needs the previous one's result, so one tool
+/// call per turn.
+const MUTATION_PARAMS: GenerationParams = GenerationParams {
+    temperature: Some(0.2),
+    max_tokens: Some(8192),
+    seed: None,
+    parallel_tool_calls: Some(false),
+};
+
+/// Naming wants the single most obvious identifier, not a creative one.
+const NAMING_PARAMS: GenerationParams = GenerationParams {
+    temperature: Some(0.0),
+    max_tokens: Some(32),
+    seed: None,
+    parallel_tool_calls: None,
+};
+
 /// An agentic mutation engine that creates Rust-based capabilities.
 pub struct MutationAgent<'a, C: AiClient> {
     client: &'a C,
     capabilities_root: &'a str,
+    registry: &'a dyn RegistryBackend,
     max_steps: usize,
     tool_handler: ToolHandler,
+    /// Token usage of the current mutation.
+    usage: UsageMeter,
+    /// The capability being created and its parent.
+    new_id: String,
+    parent_id: String,
+    /// Set once `complete` succeeds.
+    completed: Option<MutationResult>,
+    printer: StreamPrinter,
 }
 
 /// Result of a successful mutation.
@@ -39,37 +77,83 @@ pub struct MutationResult {
 }
 
 impl<'a, C: AiClient> MutationAgent<'a, C> {
-    pub fn new(client: &'a C, capabilities_root: &'a str) -> Self {
+    pub fn new(
+        client: &'a C,
+        capabilities_root: &'a str,
+        registry: &'a dyn RegistryBackend,
+    ) -> Self {
         Self {
             client,
             capabilities_root,
+            registry,
             max_steps: 30,
             tool_handler: ToolHandler::new(capabilities_root.to_string()),
+            usage: UsageMeter::default(),
+            new_id: String::new(),
+            parent_id: String::new(),
+            completed: None,
+            printer: StreamPrinter::default(),
         }
     }
 
+    /// Token usage of the last mutation, including failed ones.
+    pub fn usage(&self) -> UsageTotals {
+        self.usage.totals()
+    }
+
     /// Mutate an existing capability to create a new one.
+    ///
+    /// Stops before the next model call once the mutation's budget
+    /// (MUTATION_TOKEN_BUDGET, MUTATION_COST_BUDGET) is spent.
     pub fn mutate_capability(&mut self, task: &str, parent_id: &str) -> Result<MutationResult> {
         // Reset tool handler state
         self.tool_handler.reset();
+        self.usage = UsageMeter::from_env(ChatRole::Mutation)?;
 
+        let result = self.mutate(task, parent_id);
+        println!("[MUTATION] Usage: {}", self.usage.totals());
+        result
+    }
+
+    fn mutate(&mut self, task: &str, parent_id: &str) -> Result<MutationResult> {
         // Step 1: Generate new capability ID and copy parent
         let new_id = self.generate_new_id(task)?;
-        let cap_ops = CapabilityOps::new(self.capabilities_root);
+        let cap_ops = CapabilityOps::new(self.capabilities_root, self.registry);
         cap_ops.copy_capability(parent_id, &new_id)?;
 
         println!("[MUTATION] Created '{}' from '{}'", new_id, parent_id);
 
+        let result = self.build_capability(task, parent_id, &new_id);
+
+        // Whatever went wrong (budget, max_steps, a failing tool), a crate
+        // that never got registered mustn't be left around to be loaded.
+        if let Err(e) = &result {
+            if self.registry.get(&new_id)?.is_none() {
+                cap_ops.discard(&new_id)?;
+                println!("[MUTATION] Discarded '{}': {}", new_id, e);
+            }
+        }
+
+        result
+    }
+
+    /// Have the model turn the copied crate into the new capability.
+    fn build_capability(
+        &mut self,
+        task: &str,
+        parent_id: &str,
+        new_id: &str,
+    ) -> Result<MutationResult> {
         // Step 2: Read current state and build prompt
         let new_cap_path = Path::new(self.capabilities_root)
             .join("crates")
-            .join(&new_id);
+            .join(new_id);
         let main_rs_content = std::fs::read_to_string(new_cap_path.join("src/main.rs"))
             .with_context(|| format!("Failed to read {}/src/main.rs", new_cap_path.display()))?;
 
         let system_prompt = build_system_prompt(
             self.capabilities_root,
-            &new_id,
+            new_id,
             &new_cap_path,
             &main_rs_content,
             task,
@@ -78,9 +162,7 @@ impl<'a, C: AiClient> MutationAgent<'a, C> {
         println!("[MUTATION] Task: {}", task);
 
         // Step 3: Run the agent loop
-        let result = self.run_agent_loop(&new_id, parent_id, task, system_prompt)?;
-
-        Ok(result)
+        self.run_agent_loop(new_id, parent_id, system_prompt)
     }
 
     /// Run the main agent loop until completion or max steps.
@@ -88,117 +170,36 @@ impl<'a, C: AiClient> MutationAgent<'a, C> {
         &mut self,
         new_id: &str,
         parent_id: &str,
-        _task: &str,
         system_prompt: String,
     ) -> Result<MutationResult> {
-        let tools = TOOL_DEFINITIONS.clone();
-
-        let mut messages = vec![
-            json!({ "role": "system", "content": system_prompt }),
-            json!({ "role": "user", "content": format!("Create the capability. Start by writing the updated src/main.rs.") }),
-        ];
-
-        for step in 0..self.max_steps {
-            println!("\n[STEP {}]", step + 1);
-
-            let request = ChatRequest::new(messages.clone()).with_tools(tools.clone());
-            let response = self.client.chat(request)?;
-
-            let choice = response
-                .choices
-                .into_iter()
-                .next()
-                .context("no choices in chat response")?;
-
-            let msg = choice.message;
-
-            // Handle tool calls
-            if let Some(tool_calls) = msg.tool_calls.clone() {
-                // Push assistant message with tool calls
-                let assistant_msg = json!({
-                    "role": "assistant",
-                    "content": msg.content.clone(),
-                    "tool_calls": tool_calls.iter().map(|tc| {
-                        json!({
-                            "id": tc.id,
-                            "type": tc.call_type,
-                            "function": {
-                                "name": tc.function.name,
-                                "arguments": tc.function.arguments,
-                            }
-                        })
-                    }).collect::<Vec<_>>()
-                });
-                messages.push(assistant_msg);
-
-                // Handle each tool call
-                for tc in tool_calls {
-                    // Check if this is a completion attempt
-                    if tc.function.name == "complete" {
-                        if let Some(result) =
-                            self.try_complete(&tc, new_id, parent_id, &mut messages)?
-                        {
-                            return Ok(result);
-                        }
-                        continue;
-                    }
-
-                    // Handle regular tool call
-                    let result = self.tool_handler.handle(&tc, new_id)?;
-                    messages.push(json!({
-                        "role": "tool",
-                        "tool_call_id": tc.id,
-                        "name": tc.function.name,
-                        "content": result,
-                    }));
-                }
-
-                continue;
-            }
-
-            // No tool calls - agent is responding with text
-            let content = msg.content.unwrap_or_default();
-            if !content.is_empty() {
-                println!("[MUTATION] {}", content);
-            }
-
-            // Check if the agent seems to be giving up
-            if self.is_giving_up(&content) {
-                println!("[MUTATION] Agent indicated task cannot be completed. Exiting.");
-                anyhow::bail!(
-                    "Mutation agent indicated task cannot be completed: {}",
-                    content
-                );
-            }
-
-            messages.push(json!({ "role": "assistant", "content": content }));
-            messages.push(json!({
-                "role": "user",
-                "content": "Continue with the implementation. Use the tools to write code, build it, test it, and call complete() when done."
-            }));
-        }
+        let params = GenerationParams::from_env("MUTATION", MUTATION_PARAMS)?;
+        let context = ContextPolicy::from_env("MUTATION")?;
+        self.new_id = new_id.to_string();
+        self.parent_id = parent_id.to_string();
+        self.completed = None;
+
+        let mut conversation = Conversation::new(
+            system_prompt,
+            "Create the capability. Start by writing the updated src/main.rs.",
+        );
 
-        anyhow::bail!("Mutation agent reached max_steps without completing")
+        let client = self.client;
+        ToolLoop::new(client, self.max_steps)
+            .with_params(params)
+            .with_context_policy(context)
+            .run(&mut conversation, self)?
+            .context("Mutation agent reached max_steps without completing")
     }
 
-    /// Try to complete the mutation, returns Some(result) on success, None if not ready.
-    fn try_complete(
-        &mut self,
-        tc: &se_runtime_core::ai_client::ChatToolCall,
-        new_id: &str,
-        parent_id: &str,
-        messages: &mut Vec<serde_json::Value>,
-    ) -> Result<Option<MutationResult>> {
+    /// Try to complete the mutation. On success the result is kept in
+    /// `completed`; otherwise the returned text tells the model what's missing.
+    fn try_complete(&mut self, tc: &ToolCall) -> Result<String> {
+        let new_id = self.new_id.clone();
+        let parent_id = self.parent_id.as_str();
         let completion: CompletionArgs = match serde_json::from_str(&tc.function.arguments) {
             Ok(a) => a,
             Err(e) => {
-                messages.push(json!({
-                    "role": "tool",
-                    "tool_call_id": tc.id,
-                    "name": tc.function.name,
-                    "content": format!("ERROR: Invalid arguments. Need 'summary'. {}", e),
-                }));
-                return Ok(None);
+                return Ok(format!("ERROR: Invalid arguments. Need 'summary'. {}", e));
             }
         };
 
@@ -216,36 +217,37 @@ impl<'a, C: AiClient> MutationAgent<'a, C> {
                 "ERROR: Cannot complete yet. Missing steps:\n- {}\n\nComplete these steps first, then call complete() again.",
                 missing.join("\n- ")
             );
-            messages.push(json!({
-                "role": "tool",
-                "tool_call_id": tc.id,
-                "name": tc.function.name,
-                "content": error_msg,
-            }));
-            return Ok(None);
+            return Ok(error_msg);
         }
 
-        // Update meta.json with final summary
-        let cap_ops = CapabilityOps::new(self.capabilities_root);
-        cap_ops.update_meta_json(new_id, &completion.summary)?;
+        // Register the capability with its final summary
+        let cap_ops = CapabilityOps::new(self.capabilities_root, self.registry);
+        cap_ops.register(
+            &new_id,
+            &completion.summary,
+            completion.input_schema.as_ref(),
+            completion.output_schema.as_ref(),
+            &completion.tags,
+        )?;
 
         // Mark parent as legacy if requested
         if completion.mark_parent_legacy {
-            if let Err(e) = cap_ops.mark_as_legacy(parent_id, new_id) {
+            if let Err(e) = cap_ops.mark_as_legacy(parent_id, &new_id) {
                 println!("[MUTATION] Warning: Failed to mark parent as legacy: {}", e);
             }
         }
 
         println!("[MUTATION] Complete! Created: {}", new_id);
 
-        Ok(Some(MutationResult {
-            capability_id: new_id.to_string(),
+        self.completed = Some(MutationResult {
+            capability_id: new_id.clone(),
             summary: completion.summary,
-        }))
+        });
+        Ok(format!("Capability '{}' registered.", new_id))
     }
 
     /// Generate a short, descriptive capability ID from the task using the LLM.
-    fn generate_new_id(&self, task: &str) -> Result<String> {
+    fn generate_new_id(&mut self, task: &str) -> Result<String> {
         let crates_dir = Path::new(self.capabilities_root).join("crates");
 
         let naming_prompt = format!(
@@ -258,23 +260,29 @@ Rules:
 - Keep it short (2-4 words max, e.g. "get_weather", "sort_json", "fetch_crypto_price")
 - Be descriptive of what it does
 - No numbers or version suffixes
-- Just output the identifier, nothing else"#,
+- Respond with JSON only: {{"id": "<identifier>"}}"#,
             task
         );
 
-        let request = ChatRequest::new(vec![json!({
-            "role": "user",
-            "content": naming_prompt
-        })]);
+        let request = ChatRequest::new(vec![Message::user(naming_prompt)])
+            .with_params(&NAMING_PARAMS)
+            .with_response_format(ResponseFormat::JsonObject);
 
         let response = self.client.chat(request)?;
-        let raw_name = response
+        self.usage.record(&response);
+        let content = response
             .choices
             .into_iter()
             .next()
             .and_then(|c| c.message.content)
             .unwrap_or_else(|| "new_capability".to_string());
 
+        // Servers without JSON mode may still answer with the bare name.
+        let raw_name = serde_json::from_str::<serde_json::Value>(&content)
+            .ok()
+            .and_then(|v| v.get("id").and_then(|id| id.as_str()).map(str::to_string))
+            .unwrap_or(content);
+
         // Clean up the name
         let base_name = raw_name
             .trim()
@@ -313,3 +321,172 @@ Rules:
             || lower.contains("impossible")
     }
 }
+
+impl<C: AiClient> ToolSet for MutationAgent<'_, C> {
+    fn definitions(&self) -> Vec<serde_json::Value> {
+        TOOL_DEFINITIONS.clone()
+    }
+
+    fn dispatch(&mut self, tc: &ToolCall) -> Result<String> {
+        // Check if this is a completion attempt
+        if tc.function.name == "complete" {
+            return self.try_complete(tc);
+        }
+        self.tool_handler.handle(tc, &self.new_id)
+    }
+
+    fn output_limit(&self, tool: &str) -> Option<OutputLimit> {
+        match tool {
+            // Compiler and test output: the first error is near the top,
+            // the outcome at the bottom.
+            "build" | "test" | "cargo_run" => Some(OutputLimit::new(8_000, Keep::HeadAndTail)),
+            // Source files are read top-down; capability_common alone is
+            // ~1400 lines.
+            "read_file" => Some(OutputLimit::new(24_000, Keep::Head)),
+            "http_get" | "web_search" | "rustc_explain" => {
+                Some(OutputLimit::new(4_000, Keep::Head))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<C: AiClient> LoopHooks for MutationAgent<'_, C> {
+    type Output = MutationResult;
+
+    fn on_step(&mut self, step: usize) -> Result<()> {
+        println!("\n[STEP {}]", step + 1);
+        self.usage.check()?;
+        Ok(())
+    }
+
+    fn on_delta(&mut self, delta: StreamDelta<'_>) {
+        self.printer.on_delta(delta);
+    }
+
+    fn on_compaction(&mut self, compaction: &Compaction) {
+        println!(
+            "[MUTATION] Compacted history: ~{} -> ~{} tokens ({} summarized, {} dropped)",
+            compaction.tokens_before,
+            compaction.tokens_after,
+            compaction.summarized,
+            compaction.dropped
+        );
+    }
+
+    fn on_response(&mut self, response: &ChatResponse) {
+        self.printer.finish();
+        self.usage.record(response);
+    }
+
+    fn on_tool_result(&mut self, _tc: &ToolCall, _result: &str) -> Result<Control<MutationResult>> {
+        Ok(match self.completed.take() {
+            Some(result) => Control::Stop(result),
+            None => Control::Continue,
+        })
+    }
+
+    // No tool calls - agent is responding with text
+    fn on_text(
+        &mut self,
+        content: Option<String>,
+        conversation: &mut Conversation,
+    ) -> Result<Control<MutationResult>> {
+        let content = content.unwrap_or_default();
+        if !content.is_empty() {
+            println!("[MUTATION] {}", content);
+        }
+
+        // Check if the agent seems to be giving up
+        if self.is_giving_up(&content) {
+            println!("[MUTATION] Agent indicated task cannot be completed. Exiting.");
+            anyhow::bail!(
+                "Mutation agent indicated task cannot be completed: {}",
+                content
+            );
+        }
+
+        conversation.push(Message::assistant(content));
+        conversation.push(Message::user(
+            "Continue with the implementation. Use the tools to write code, build it, test it, and call complete() when done.",
+        ));
+        Ok(Control::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+
+    use se_runtime_core::capability_registry::CapabilityRegistry;
+    use se_runtime_core::scripted_client::{text_response, tool_call_response, ScriptedClient};
+
+    use super::*;
+
+    #[test]
+    fn test_mutation_loop_with_scripted_client() {
+        let root = std::env::temp_dir().join(format!("se_mutation_{}", std::process::id()));
+        let parent = root.join("crates").join("get_car_details");
+        fs::create_dir_all(parent.join("src")).unwrap();
+        fs::write(
+            parent.join("Cargo.toml"),
+            "[package]\nname = \"get_car_details\"\n",
+        )
+        .unwrap();
+        fs::write(parent.join("src/main.rs"), "fn main() {}\n").unwrap();
+        let root_str = root.to_str().unwrap();
+        let main_rs = root.join("crates/get_car_mileage/src/main.rs");
+
+        // Name it, write the code, try to complete without building, give up.
+        let client = ScriptedClient::new(vec![
+            text_response(r#"{"id": "get_car_mileage"}"#),
+            tool_call_response(
+                "call_1",
+                "write_file",
+                json!({
+                    "path": main_rs.to_str().unwrap(),
+                    "content": "fn main() { println!(\"{}\", 42); }\n"
+                }),
+            ),
+            tool_call_response("call_2", "complete", json!({"summary": "Car mileage."})),
+            text_response("I am unable to build without the wasm target."),
+        ]);
+        let registry = CapabilityRegistry::new(&root);
+
+        let mut agent = MutationAgent::new(&client, root_str, &registry);
+        let err = agent
+            .mutate_capability("Get the mileage of an employee's car", "get_car_details")
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot be completed"));
+
+        // Never registered, so the half-built crate is gone.
+        assert!(!root.join("crates").join("get_car_mileage").exists());
+        assert!(parent.join("src/main.rs").exists());
+
+        let requests = client.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].temperature, Some(0.0));
+        assert_eq!(
+            requests[0].response_format,
+            Some(ResponseFormat::JsonObject)
+        );
+        assert_eq!(requests[1].temperature, MUTATION_PARAMS.temperature);
+        assert_eq!(requests[1].max_tokens, Some(8192));
+        assert_eq!(requests[1].parallel_tool_calls, Some(false));
+        let Some(Message::Tool(written)) = requests[2].messages.last() else {
+            panic!("expected a tool result");
+        };
+        assert!(!written.content.starts_with("ERROR"), "{}", written.content);
+        let Some(Message::Tool(refusal)) = requests[3].messages.last() else {
+            panic!("expected a tool result");
+        };
+        assert_eq!(refusal.tool_call_id, "call_2");
+        assert!(refusal.content.contains("Cannot complete yet"));
+        assert_eq!(client.remaining(), 0);
+        assert_eq!(agent.usage().calls, 4);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -180,7 +180,7 @@ These are WORKING examples that show the correct patterns. Learn from them!
 
 ### STEP 4: COMPLETE
 - Only after build AND test succeed with correct output
-- Call **complete** with a summary
+- Call **complete** with a summary, plus `input_schema` and `output_schema` (JSON Schema objects) describing stdin and stdout, and a few `tags`
 
 ### ⚠️ CRITICAL: VERIFY OUTPUT BEFORE COMPLETING
 - Do NOT just run test and immediately complete
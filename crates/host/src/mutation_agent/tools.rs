@@ -22,9 +22,9 @@ use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use se_runtime_core::ai_client::ChatToolCall;
+use se_runtime_core::ai_client::ToolCall;
 use se_runtime_core::capability_runner::CapabilityRunner;
-use se_runtime_core::types::{CapabilityRecord, CapabilityStatus};
+use se_runtime_core::types::CapabilityRecord;
 
 /// Extract search result snippets from DuckDuckGo HTML.
 fn extract_search_snippets(html: &str) -> Vec<String> {
@@ -45,7 +45,7 @@ fn extract_search_snippets(html: &str) -> Vec<String> {
                 .filter(|c| !matches!(c, '<'..='>' | '\n' | '\r'))
                 .collect::<String>()
                 .split('<')
-                .filter_map(|s| s.split('>').last())
+                .filter_map(|s| s.split('>').next_back())
                 .collect::<Vec<_>>()
                 .join(" ");
 
@@ -214,6 +214,19 @@ pub static TOOL_DEFINITIONS: Lazy<Vec<serde_json::Value>> = Lazy::new(|| {
                         "mark_parent_legacy": {
                             "type": "boolean",
                             "description": "Set to true if this capability REPLACES or IMPROVES the parent (marks parent as legacy). Set to false if this is just a new variant/derivative."
+                        },
+                        "input_schema": {
+                            "type": "object",
+                            "description": "JSON Schema of the stdin input, e.g. {\"type\": \"object\", \"properties\": {\"employee_id\": {\"type\": \"string\"}}, \"required\": [\"employee_id\"]}"
+                        },
+                        "output_schema": {
+                            "type": "object",
+                            "description": "JSON Schema of the stdout output (top-level properties are enough)."
+                        },
+                        "tags": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Short lowercase tags to help find this capability, e.g. [\"hr\", \"salary\", \"update\"]."
                         }
                     },
                     "required": ["summary"]
@@ -229,6 +242,12 @@ pub struct CompletionArgs {
     pub summary: String,
     #[serde(default)]
     pub mark_parent_legacy: bool,
+    #[serde(default)]
+    pub input_schema: Option<serde_json::Value>,
+    #[serde(default)]
+    pub output_schema: Option<serde_json::Value>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 /// Handles tool calls from the mutation agent.
@@ -272,7 +291,7 @@ impl ToolHandler {
     }
 
     /// Handle a tool call, returning the result string.
-    pub fn handle(&mut self, tc: &ChatToolCall, new_id: &str) -> Result<String> {
+    pub fn handle(&mut self, tc: &ToolCall, new_id: &str) -> Result<String> {
         match tc.function.name.as_str() {
             "web_search" => self.handle_web_search(tc),
             "http_get" => self.handle_http_get(tc),
@@ -287,7 +306,7 @@ impl ToolHandler {
         }
     }
 
-    fn handle_web_search(&self, tc: &ChatToolCall) -> Result<String> {
+    fn handle_web_search(&self, tc: &ToolCall) -> Result<String> {
         #[derive(Deserialize)]
         struct Args {
             query: String,
@@ -345,7 +364,7 @@ impl ToolHandler {
         }
     }
 
-    fn handle_http_get(&self, tc: &ChatToolCall) -> Result<String> {
+    fn handle_http_get(&self, tc: &ToolCall) -> Result<String> {
         #[derive(Deserialize)]
         struct Args {
             url: String,
@@ -394,7 +413,7 @@ impl ToolHandler {
         }
     }
 
-    fn handle_read_file(&self, tc: &ChatToolCall) -> Result<String> {
+    fn handle_read_file(&self, tc: &ToolCall) -> Result<String> {
         #[derive(Deserialize)]
         struct Args {
             path: String,
@@ -413,7 +432,7 @@ impl ToolHandler {
         }
     }
 
-    fn handle_write_file(&mut self, tc: &ChatToolCall) -> Result<String> {
+    fn handle_write_file(&mut self, tc: &ToolCall) -> Result<String> {
         #[derive(Deserialize)]
         struct Args {
             path: String,
@@ -459,7 +478,7 @@ impl ToolHandler {
         }
     }
 
-    fn handle_cargo_run(&self, tc: &ChatToolCall, new_id: &str) -> Result<String> {
+    fn handle_cargo_run(&self, tc: &ToolCall, new_id: &str) -> Result<String> {
         #[derive(Deserialize)]
         struct Args {
             input: String,
@@ -672,7 +691,7 @@ impl ToolHandler {
         }
     }
 
-    fn handle_test(&mut self, tc: &ChatToolCall, new_id: &str) -> Result<String> {
+    fn handle_test(&mut self, tc: &ToolCall, new_id: &str) -> Result<String> {
         #[derive(Deserialize)]
         struct Args {
             input: String,
@@ -717,15 +736,11 @@ impl ToolHandler {
         };
 
         let cap = CapabilityRecord {
-            id: new_id.to_string(),
-            summary: "test".to_string(),
-            embedding: None,
             binary: Some(format!(
                 "../../target/wasm32-wasip1/release/{}.wasm",
                 new_id
             )),
-            status: CapabilityStatus::Active,
-            replaced_by: None,
+            ..CapabilityRecord::new(new_id, "test")
         };
 
         match runner.run_capability(&cap, &args.input) {
@@ -760,7 +775,7 @@ impl ToolHandler {
                                     let input_str = input_val.to_string();
                                     let output_str = output_json.to_string();
 
-                                    if !output_str.contains(&input_str.trim_matches('"')) {
+                                    if !output_str.contains(input_str.trim_matches('"')) {
                                         missing_updates.push(format!("{}: {}", key, input_val));
                                     }
                                 }
@@ -894,7 +909,7 @@ impl ToolHandler {
         }
     }
 
-    fn handle_rustc_explain(&self, tc: &ChatToolCall) -> Result<String> {
+    fn handle_rustc_explain(&self, tc: &ToolCall) -> Result<String> {
         #[derive(Deserialize)]
         struct Args {
             error_code: String,
@@ -950,7 +965,7 @@ impl ToolHandler {
         }
     }
 
-    fn handle_complete(&self, tc: &ChatToolCall) -> Result<String> {
+    fn handle_complete(&self, tc: &ToolCall) -> Result<String> {
         let args: CompletionArgs = match serde_json::from_str(&tc.function.arguments) {
             Ok(a) => a,
             Err(e) => return Ok(format!("ERROR: Invalid arguments. Need 'summary'. {}", e)),
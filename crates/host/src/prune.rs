@@ -0,0 +1,463 @@
+// crates/host/src/prune.rs
+
+//! Capability pruning.
+//!
+//! Finds capabilities that are no longer worth keeping and archives them:
+//! the registry record, the crate source (with its history) and the WASM
+//! artifact are moved under `<capabilities_root>/archive/<id>-<timestamp>/`,
+//! and the capability is removed from the registry. Moving the crate out of
+//! `crates/` drops it from the capabilities workspace, and the index is
+//! rebuilt from the registry on the next load.
+//!
+//! Usage: `se_runtime_host prune [--dry-run] [--root DIR]`
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+use se_runtime_core::registry_backend::{
+    registry_from_env, RegistryBackend, RegistryChange, RegistryEvent,
+};
+use se_runtime_core::types::{CapabilityRecord, CapabilityStatus};
+
+/// Directory under the capabilities root that pruned capabilities move to.
+pub const ARCHIVE_DIR: &str = "archive";
+
+const ACTOR: &str = "prune";
+
+/// When a capability gets pruned.
+///
+/// - retired: always
+/// - deprecated: once it has been deprecated for `deprecated_days`
+/// - legacy: once its replacement is registered
+/// - active: only if `unused_days` is set and its history shows it was
+///   registered that long ago without it ever running
+///
+/// A capability still named as `replaced_by` by one that is kept is never
+/// pruned.
+#[derive(Debug, Clone)]
+pub struct PrunePolicy {
+    pub deprecated_days: i64,
+    pub unused_days: Option<i64>,
+}
+
+impl PrunePolicy {
+    /// Read policy from environment variables.
+    ///
+    /// - PRUNE_DEPRECATED_DAYS (default: 30)
+    /// - PRUNE_UNUSED_DAYS (default: unset, never-used active capabilities are kept)
+    pub fn from_env() -> Result<Self> {
+        let deprecated_days = match std::env::var("PRUNE_DEPRECATED_DAYS") {
+            Ok(v) => v
+                .parse()
+                .with_context(|| format!("invalid PRUNE_DEPRECATED_DAYS '{}'", v))?,
+            Err(_) => 30,
+        };
+        let unused_days = match std::env::var("PRUNE_UNUSED_DAYS") {
+            Ok(v) => Some(
+                v.parse()
+                    .with_context(|| format!("invalid PRUNE_UNUSED_DAYS '{}'", v))?,
+            ),
+            Err(_) => None,
+        };
+
+        Ok(Self {
+            deprecated_days,
+            unused_days,
+        })
+    }
+}
+
+/// A capability selected for pruning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PruneCandidate {
+    pub id: String,
+    pub reason: String,
+}
+
+/// Pick the capabilities `policy` would prune.
+///
+/// `history` holds each capability's registry events (used to date status
+/// changes and registration); `has_run` says whether it has ever run.
+pub fn plan(
+    policy: &PrunePolicy,
+    records: &[CapabilityRecord],
+    history: &HashMap<String, Vec<RegistryEvent>>,
+    has_run: impl Fn(&str) -> bool,
+    now: DateTime<Utc>,
+) -> Vec<PruneCandidate> {
+    let by_id: HashMap<&str, &CapabilityRecord> =
+        records.iter().map(|r| (r.id.as_str(), r)).collect();
+    let days_since = |at: &str| {
+        DateTime::parse_from_rfc3339(at)
+            .ok()
+            .map(|t| (now - t.with_timezone(&Utc)).num_days())
+    };
+    let no_events = Vec::new();
+
+    let mut candidates: Vec<PruneCandidate> = Vec::new();
+    for record in records {
+        let events = history.get(&record.id).unwrap_or(&no_events);
+
+        let reason = match record.status {
+            CapabilityStatus::Retired => Some("retired".to_string()),
+            CapabilityStatus::Deprecated => events
+                .iter()
+                .rev()
+                .find(|e| {
+                    matches!(
+                        e.change,
+                        RegistryChange::StatusChanged {
+                            to: CapabilityStatus::Deprecated,
+                            ..
+                        }
+                    )
+                })
+                .and_then(|e| days_since(&e.at))
+                .filter(|days| *days >= policy.deprecated_days)
+                .map(|days| format!("deprecated for {} days", days)),
+            // Only once the replacement works; otherwise this is the
+            // last working fallback.
+            CapabilityStatus::Legacy => record
+                .replaced_by
+                .as_deref()
+                .filter(|r| {
+                    by_id
+                        .get(r)
+                        .is_some_and(|r| r.status == CapabilityStatus::Active)
+                })
+                .map(|r| format!("superseded by {}", r)),
+            CapabilityStatus::Active => policy
+                .unused_days
+                .filter(|_| !has_run(&record.id))
+                .and_then(|limit| {
+                    events
+                        .first()
+                        .and_then(|e| days_since(&e.at))
+                        .filter(|days| *days >= limit)
+                })
+                .map(|days| format!("never used in {} days", days)),
+        };
+
+        if let Some(reason) = reason {
+            candidates.push(PruneCandidate {
+                id: record.id.clone(),
+                reason,
+            });
+        }
+    }
+
+    // Keep anything a surviving capability still points at, and a legacy
+    // capability whose replacement is being pruned too, until stable.
+    loop {
+        let pruned: HashSet<String> = candidates.iter().map(|c| c.id.clone()).collect();
+        let needed: HashSet<&str> = records
+            .iter()
+            .filter(|r| !pruned.contains(&r.id))
+            .filter_map(|r| r.replaced_by.as_deref())
+            .collect();
+        let replacement_pruned = |id: &str| {
+            by_id.get(id).is_some_and(|r| {
+                r.status == CapabilityStatus::Legacy
+                    && r.replaced_by.as_ref().is_some_and(|to| pruned.contains(to))
+            })
+        };
+
+        let before = candidates.len();
+        candidates.retain(|c| !needed.contains(c.id.as_str()) && !replacement_pruned(&c.id));
+        if candidates.len() == before {
+            return candidates;
+        }
+    }
+}
+
+/// Archive a capability and remove it from the registry.
+///
+/// Returns the archive directory.
+pub fn archive(
+    capabilities_root: &str,
+    registry: &dyn RegistryBackend,
+    record: &CapabilityRecord,
+    reason: &str,
+    now: DateTime<Utc>,
+) -> Result<PathBuf> {
+    let root = Path::new(capabilities_root);
+    let crate_dir = root.join("crates").join(&record.id);
+    let archive_dir =
+        root.join(ARCHIVE_DIR)
+            .join(format!("{}-{}", record.id, now.format("%Y%m%dT%H%M%SZ")));
+
+    // Remove first: the fs backend needs meta.json in place to do it, and its
+    // history (with the removal) then moves into the archive with the crate.
+    registry.remove(&record.id, ACTOR, Some(reason))?;
+
+    if let Err(err) = move_to_archive(&crate_dir, &archive_dir, record) {
+        // Put the record back so a failed move doesn't orphan the files.
+        registry.upsert(record).with_context(|| {
+            format!(
+                "failed to restore '{}' after archiving failed: {:#}",
+                record.id, err
+            )
+        })?;
+        return Err(err);
+    }
+
+    Ok(archive_dir)
+}
+
+fn move_to_archive(crate_dir: &Path, archive_dir: &Path, record: &CapabilityRecord) -> Result<()> {
+    fs::create_dir_all(archive_dir)
+        .with_context(|| format!("failed to create {}", archive_dir.display()))?;
+    fs::write(
+        archive_dir.join("record.json"),
+        serde_json::to_string_pretty(record)? + "\n",
+    )?;
+
+    // The binary path is relative to the crate directory, so move it first.
+    if let Some(binary) = &record.binary {
+        let artifact = crate_dir.join(binary);
+        if let (true, Some(name)) = (artifact.exists(), artifact.file_name()) {
+            fs::rename(&artifact, archive_dir.join(name))
+                .with_context(|| format!("failed to archive {}", artifact.display()))?;
+        }
+    }
+
+    if crate_dir.exists() {
+        fs::rename(crate_dir, archive_dir.join("crate"))
+            .with_context(|| format!("failed to archive {}", crate_dir.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Entry point for `se_runtime_host prune ...`.
+pub fn run_cli(args: &[String]) -> Result<()> {
+    let mut root = "capabilities".to_string();
+    let mut dry_run = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--root" => {
+                root = iter.next().context("--root needs a value")?.clone();
+            }
+            "--dry-run" => dry_run = true,
+            other => anyhow::bail!("unexpected argument: {}", other),
+        }
+    }
+
+    let policy = PrunePolicy::from_env()?;
+    let registry = registry_from_env(&root)?;
+    let records = registry.load()?;
+    let stats = registry.run_stats()?;
+
+    let mut history = HashMap::new();
+    for record in &records {
+        history.insert(record.id.clone(), registry.history(&record.id)?);
+    }
+
+    let now = Utc::now();
+    let candidates = plan(
+        &policy,
+        &records,
+        &history,
+        |id| stats.get(id).is_some_and(|s| s.runs() > 0),
+        now,
+    );
+
+    if candidates.is_empty() {
+        println!(
+            "[PRUNE] Nothing to prune among {} capabilities",
+            records.len()
+        );
+        return Ok(());
+    }
+
+    for candidate in &candidates {
+        if dry_run {
+            println!(
+                "[PRUNE] Would prune '{}': {}",
+                candidate.id, candidate.reason
+            );
+            continue;
+        }
+
+        let record = records
+            .iter()
+            .find(|r| r.id == candidate.id)
+            .context("candidate not in registry")?;
+        let dir = archive(&root, registry.as_ref(), record, &candidate.reason, now)?;
+        println!(
+            "[PRUNE] Pruned '{}' ({}), archived to {}",
+            candidate.id,
+            candidate.reason,
+            dir.display()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, status: CapabilityStatus, replaced_by: Option<&str>) -> CapabilityRecord {
+        CapabilityRecord {
+            status,
+            replaced_by: replaced_by.map(|r| r.to_string()),
+            ..CapabilityRecord::new(id, id)
+        }
+    }
+
+    fn event(id: &str, change: RegistryChange, at: &str) -> RegistryEvent {
+        RegistryEvent {
+            capability_id: id.to_string(),
+            at: at.to_string(),
+            change,
+            actor: None,
+            reason: None,
+        }
+    }
+
+    fn deprecated_at(id: &str, at: &str) -> (String, Vec<RegistryEvent>) {
+        let change = RegistryChange::StatusChanged {
+            from: CapabilityStatus::Active,
+            to: CapabilityStatus::Deprecated,
+            replaced_by: None,
+        };
+        (id.to_string(), vec![event(id, change, at)])
+    }
+
+    #[test]
+    fn test_plan_applies_policy_and_keeps_dependencies() {
+        use CapabilityStatus::*;
+
+        let records = vec![
+            record("old_broken", Deprecated, None),
+            // Recently deprecated after being superseded, so it's kept and
+            // so is the capability it still points at.
+            record("new_broken", Deprecated, Some("old_pinned")),
+            record("gone", Retired, None),
+            record("v1", Legacy, Some("v2")),
+            record("v2", Active, None),
+            // Replacement isn't working, or is being pruned itself.
+            record("w1", Legacy, Some("w2")),
+            record("w2", Deprecated, None),
+            record("x1", Legacy, Some("x2")),
+            record("x2", Active, None),
+            record("orphan", Legacy, Some("missing")),
+            record("idle", Active, None),
+            record("busy", Active, None),
+            record("old_pinned", Deprecated, None),
+        ];
+        let history: HashMap<String, Vec<RegistryEvent>> = [
+            deprecated_at("old_broken", "2026-01-01T00:00:00Z"),
+            deprecated_at("new_broken", "2026-10-10T00:00:00Z"),
+            deprecated_at("old_pinned", "2026-01-01T00:00:00Z"),
+            (
+                "idle".to_string(),
+                vec![event(
+                    "idle",
+                    RegistryChange::Upserted,
+                    "2026-06-01T00:00:00Z",
+                )],
+            ),
+            (
+                "x2".to_string(),
+                vec![event(
+                    "x2",
+                    RegistryChange::Upserted,
+                    "2026-06-01T00:00:00Z",
+                )],
+            ),
+            (
+                "busy".to_string(),
+                vec![event(
+                    "busy",
+                    RegistryChange::Upserted,
+                    "2026-06-01T00:00:00Z",
+                )],
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let now = DateTime::parse_from_rfc3339("2026-10-18T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let policy = PrunePolicy {
+            deprecated_days: 30,
+            unused_days: Some(90),
+        };
+        let ids = |c: Vec<PruneCandidate>| c.into_iter().map(|c| c.id).collect::<Vec<_>>();
+
+        assert_eq!(
+            ids(plan(&policy, &records, &history, |id| id == "busy", now)),
+            vec!["old_broken", "gone", "v1", "idle"]
+        );
+
+        let policy = PrunePolicy {
+            unused_days: None,
+            ..policy
+        };
+        assert_eq!(
+            ids(plan(&policy, &records, &history, |_| false, now)),
+            vec!["old_broken", "gone", "v1", "x1"]
+        );
+    }
+
+    #[test]
+    fn test_archive_moves_files_and_removes_from_registry() {
+        use se_runtime_core::capability_registry::CapabilityRegistry;
+
+        let root = std::env::temp_dir().join(format!("se_prune_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for id in ["gone", "stuck"] {
+            fs::create_dir_all(root.join("crates").join(id).join("src")).unwrap();
+            fs::write(
+                root.join("crates").join(id).join("src/main.rs"),
+                "fn main() {}",
+            )
+            .unwrap();
+        }
+        let registry = CapabilityRegistry::new(&root);
+        let gone = CapabilityRecord {
+            binary: Some("gone.wasm".to_string()),
+            ..record("gone", CapabilityStatus::Retired, None)
+        };
+        let stuck = record("stuck", CapabilityStatus::Retired, None);
+        registry.upsert(&gone).unwrap();
+        registry.upsert(&stuck).unwrap();
+        fs::write(root.join("crates/gone/gone.wasm"), b"wasm").unwrap();
+
+        let now = DateTime::parse_from_rfc3339("2026-10-18T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let root_str = root.to_str().unwrap();
+
+        // A failed move puts the record back.
+        fs::write(root.join(ARCHIVE_DIR), "not a directory").unwrap();
+        assert!(archive(root_str, &registry, &stuck, "retired", now).is_err());
+        assert!(registry.get("stuck").unwrap().is_some());
+        assert!(root.join("crates/stuck/src/main.rs").exists());
+        fs::remove_file(root.join(ARCHIVE_DIR)).unwrap();
+
+        let dir = archive(root_str, &registry, &gone, "retired", now).unwrap();
+        assert_eq!(dir, root.join("archive/gone-20261018T000000Z"));
+        assert!(registry.get("gone").unwrap().is_none());
+        assert!(!root.join("crates/gone").exists());
+        assert!(dir.join("gone.wasm").exists());
+        assert!(dir.join("crate/src/main.rs").exists());
+        let archived: CapabilityRecord =
+            serde_json::from_str(&fs::read_to_string(dir.join("record.json")).unwrap()).unwrap();
+        assert_eq!(archived.id, "gone");
+        let history = fs::read_to_string(dir.join("crate/history.jsonl")).unwrap();
+        assert!(history.lines().last().unwrap().contains("\"removed\""));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
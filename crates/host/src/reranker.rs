@@ -0,0 +1,286 @@
+// crates/host/src/reranker.rs
+
+//! Optional LLM second stage for capability retrieval.
+//!
+//! The index shortlists the top-N capabilities by embedding similarity; a
+//! reranker then scores each candidate's summary and schemas against the
+//! task so the agent sees the ones that actually fit.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use se_runtime_core::ai_client::{
+    AiClient, ChatRequest, GenerationParams, Message, ResponseFormat,
+};
+use se_runtime_core::types::CapabilityRecord;
+use se_runtime_core::usage::UsageMeter;
+
+/// Default number of index candidates handed to the reranker.
+const DEFAULT_CANDIDATES: usize = 8;
+/// Default minimum reranker score (0..1) for a candidate to count as a match.
+const DEFAULT_MIN_SCORE: f32 = 0.5;
+
+/// Grading should be repeatable for the same task and candidates.
+const RERANK_PARAMS: GenerationParams = GenerationParams {
+    temperature: Some(0.0),
+    max_tokens: None,
+    seed: None,
+    parallel_tool_calls: None,
+};
+
+/// Relevance judgement for one candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RerankScore {
+    pub id: String,
+    /// Relevance in 0..1.
+    pub score: f32,
+    pub reason: String,
+}
+
+/// Second-stage scorer over retrieved capability candidates.
+pub trait Reranker {
+    /// How many of the index's top candidates to pass to `rerank`.
+    fn candidate_count(&self) -> usize;
+
+    /// Minimum score for a candidate to be shown as a match.
+    fn min_score(&self) -> f32;
+
+    /// Score candidates for a task, best first, charging any model calls
+    /// to the task's `usage`.
+    fn rerank(
+        &self,
+        task: &str,
+        candidates: &[&CapabilityRecord],
+        usage: &mut UsageMeter,
+    ) -> Result<Vec<RerankScore>>;
+}
+
+/// Reranker that asks a chat model to grade each candidate.
+///
+/// Enabled with RERANK_ENABLED=1. Optional environment variables:
+///
+/// - RERANK_CANDIDATES (default: 8)
+/// - RERANK_MIN_SCORE (default: 0.5)
+pub struct LlmReranker<'a, C: AiClient> {
+    client: &'a C,
+    candidates: usize,
+    min_score: f32,
+}
+
+impl<'a, C: AiClient> LlmReranker<'a, C> {
+    pub fn new(client: &'a C) -> Self {
+        Self {
+            client,
+            candidates: DEFAULT_CANDIDATES,
+            min_score: DEFAULT_MIN_SCORE,
+        }
+    }
+
+    /// Build from environment variables, or `None` when RERANK_ENABLED isn't set.
+    pub fn from_env(client: &'a C) -> Result<Option<Self>> {
+        let enabled = std::env::var("RERANK_ENABLED")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+
+        let mut reranker = Self::new(client);
+        if let Ok(v) = std::env::var("RERANK_CANDIDATES") {
+            reranker.candidates = v
+                .parse()
+                .with_context(|| format!("RERANK_CANDIDATES is not a number: {}", v))?;
+        }
+        if let Ok(v) = std::env::var("RERANK_MIN_SCORE") {
+            reranker.min_score = v
+                .parse()
+                .with_context(|| format!("RERANK_MIN_SCORE is not a number: {}", v))?;
+        }
+
+        Ok(Some(reranker))
+    }
+
+    fn build_prompt(task: &str, candidates: &[&CapabilityRecord]) -> String {
+        let listing: Vec<String> = candidates
+            .iter()
+            .map(|cap| {
+                let mut entry = format!("- id: {}\n  summary: {}", cap.id, cap.summary);
+                if let Some(schema) = &cap.input_schema {
+                    entry.push_str(&format!("\n  input_schema: {}", schema));
+                }
+                if let Some(schema) = &cap.output_schema {
+                    entry.push_str(&format!("\n  output_schema: {}", schema));
+                }
+                entry
+            })
+            .collect();
+
+        format!(
+            r#"You are ranking executable capabilities for a task.
+
+## TASK
+{task}
+
+## CANDIDATES
+{listing}
+
+For EACH candidate, score from 0 to 10 how well running it (possibly more than once) solves the task:
+- 10: directly solves the task with the inputs the task provides
+- 5: related and useful, but only covers part of the task
+- 0: unrelated, or does the opposite (e.g. reads when the task must update)
+
+Respond with JSON only, no markdown:
+{{"scores": [{{"id": "<candidate id>", "score": <0-10>, "reason": "<one short sentence>"}}]}}"#,
+            task = task,
+            listing = listing.join("\n"),
+        )
+    }
+}
+
+impl<C: AiClient> Reranker for LlmReranker<'_, C> {
+    fn candidate_count(&self) -> usize {
+        self.candidates
+    }
+
+    fn min_score(&self) -> f32 {
+        self.min_score
+    }
+
+    fn rerank(
+        &self,
+        task: &str,
+        candidates: &[&CapabilityRecord],
+        usage: &mut UsageMeter,
+    ) -> Result<Vec<RerankScore>> {
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+        usage.check()?;
+
+        let prompt = Self::build_prompt(task, candidates);
+        let request = ChatRequest::new(vec![Message::user(prompt)])
+            .with_params(&RERANK_PARAMS)
+            .with_response_format(ResponseFormat::JsonObject);
+        let response = self.client.chat(request)?;
+        usage.record(&response);
+
+        let content = response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|c| c.message.content)
+            .context("reranker returned no content")?;
+
+        let known: Vec<&str> = candidates.iter().map(|c| c.id.as_str()).collect();
+        let scores = parse_scores(&content, &known)?;
+
+        for s in &scores {
+            println!("[RERANK] {} -> {:.2}: {}", s.id, s.score, s.reason);
+        }
+
+        Ok(scores)
+    }
+}
+
+/// Parse the model's JSON verdict, ignoring unknown ids and sorting best first.
+fn parse_scores(content: &str, known_ids: &[&str]) -> Result<Vec<RerankScore>> {
+    #[derive(Deserialize)]
+    struct Verdict {
+        scores: Vec<Entry>,
+    }
+
+    #[derive(Deserialize)]
+    struct Entry {
+        id: String,
+        score: f32,
+        #[serde(default)]
+        reason: String,
+    }
+
+    // Tolerate code fences or chatter around the JSON object.
+    let start = content
+        .find('{')
+        .context("reranker response contained no JSON")?;
+    let end = content
+        .rfind('}')
+        .context("reranker response contained no JSON")?;
+    let verdict: Verdict = serde_json::from_str(&content[start..=end])
+        .context("failed to parse reranker response JSON")?;
+
+    let mut scores: Vec<RerankScore> = Vec::new();
+    for entry in verdict.scores {
+        if !known_ids.contains(&entry.id.as_str()) || scores.iter().any(|s| s.id == entry.id) {
+            continue;
+        }
+        scores.push(RerankScore {
+            id: entry.id,
+            score: (entry.score / 10.0).clamp(0.0, 1.0),
+            reason: entry.reason,
+        });
+    }
+
+    scores.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Ok(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scores_normalises_and_sorts() {
+        let content = r#"```json
+{"scores": [
+  {"id": "get_car_details", "score": 2, "reason": "unrelated"},
+  {"id": "get_salary_details", "score": 9, "reason": "returns base salary"},
+  {"id": "made_up", "score": 10, "reason": "not a candidate"}
+]}
+```"#;
+
+        let scores = parse_scores(content, &["get_salary_details", "get_car_details"]).unwrap();
+
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores[0].id, "get_salary_details");
+        assert!((scores[0].score - 0.9).abs() < 1e-6);
+        assert_eq!(scores[1].reason, "unrelated");
+    }
+
+    #[test]
+    fn test_parse_scores_rejects_non_json() {
+        assert!(parse_scores("I think salary is best.", &["a"]).is_err());
+    }
+
+    #[test]
+    fn test_rerank_charges_the_task_budget() {
+        use se_runtime_core::scripted_client::{text_response, ScriptedClient};
+        use se_runtime_core::usage::Budget;
+
+        let client = ScriptedClient::new(vec![text_response(
+            r#"{"scores": [{"id": "get_salary_details", "score": 8}]}"#,
+        )]);
+        let reranker = LlmReranker::new(&client);
+        let salary = CapabilityRecord::new("get_salary_details", "Returns salary details.");
+
+        let mut usage = UsageMeter::default();
+        let scores = reranker
+            .rerank("What does EMP001 earn?", &[&salary], &mut usage)
+            .unwrap();
+        assert_eq!(scores[0].id, "get_salary_details");
+        assert_eq!(usage.totals().calls, 1);
+
+        // A spent budget stops it before the model is called.
+        let budget = Budget {
+            max_tokens: Some(0),
+            max_cost: None,
+        };
+        let mut spent = UsageMeter::new(None, budget).unwrap();
+        assert!(reranker
+            .rerank("What does EMP001 earn?", &[&salary], &mut spent)
+            .is_err());
+        assert_eq!(spent.totals().calls, 0);
+    }
+}
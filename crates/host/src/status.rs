@@ -0,0 +1,174 @@
+// crates/host/src/status.rs
+
+//! Manual capability lifecycle commands.
+//!
+//! Status changes go through `lifecycle::Lifecycle`, so only allowed
+//! transitions are made and each one is recorded with "cli" as the actor.
+//!
+//! Usage:
+//!   `se_runtime_host revive <id> [--reason TEXT] [--force] [--root DIR]`
+//!   `se_runtime_host retire <id> [--reason TEXT] [--root DIR]`
+//!   `se_runtime_host history <id> [--root DIR]`
+
+use anyhow::{Context, Result};
+
+use se_runtime_core::lifecycle::{is_allowed, Lifecycle};
+use se_runtime_core::registry_backend::{registry_from_env, RegistryChange, RegistryEvent};
+use se_runtime_core::registry_health::{validate_records, IssueKind, Severity};
+use se_runtime_core::types::CapabilityStatus;
+
+const ACTOR: &str = "cli";
+
+struct Args {
+    id: String,
+    root: String,
+    reason: Option<String>,
+    force: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Args> {
+    let mut id = None;
+    let mut root = "capabilities".to_string();
+    let mut reason = None;
+    let mut force = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--root" => {
+                root = iter.next().context("--root needs a value")?.clone();
+            }
+            "--reason" => {
+                reason = Some(iter.next().context("--reason needs a value")?.clone());
+            }
+            "--force" => force = true,
+            other if other.starts_with("--") => {
+                anyhow::bail!("unexpected argument: {}", other)
+            }
+            other => {
+                if id.is_some() {
+                    anyhow::bail!("unexpected argument: {}", other);
+                }
+                id = Some(other.to_string());
+            }
+        }
+    }
+
+    Ok(Args {
+        id: id.context("missing capability id")?,
+        root,
+        reason,
+        force,
+    })
+}
+
+/// Entry point for `se_runtime_host revive ...`.
+///
+/// The capability is revalidated first; errors or a missing binary block the
+/// revive unless `--force` is given.
+pub fn run_revive(args: &[String]) -> Result<()> {
+    let args = parse_args(args)?;
+    let registry = registry_from_env(&args.root)?;
+
+    let records = registry.load()?;
+    let record = records
+        .iter()
+        .find(|r| r.id == args.id)
+        .with_context(|| format!("Capability '{}' not found", args.id))?;
+    if !is_allowed(&record.status, &CapabilityStatus::Active) {
+        anyhow::bail!("'{}' is {:?} and can't be revived", args.id, record.status);
+    }
+
+    let blocking: Vec<String> = validate_records(&records, &args.root)
+        .into_iter()
+        .filter(|i| i.capability_id == args.id)
+        .filter(|i| {
+            i.severity() == Severity::Error
+                || matches!(i.kind, IssueKind::NoBinary | IssueKind::MissingBinary(_))
+        })
+        .map(|i| i.kind.to_string())
+        .collect();
+
+    if !blocking.is_empty() {
+        if !args.force {
+            anyhow::bail!(
+                "'{}' failed revalidation (use --force to revive anyway):\n  {}",
+                args.id,
+                blocking.join("\n  ")
+            );
+        }
+        println!(
+            "[LIFECYCLE] Reviving '{}' despite: {}",
+            args.id,
+            blocking.join("; ")
+        );
+    }
+
+    let reason = args
+        .reason
+        .as_deref()
+        .unwrap_or("revived from the command line");
+    Lifecycle::new(registry.as_ref()).revive(&args.id, ACTOR, reason)?;
+    println!("[LIFECYCLE] '{}' is active again", args.id);
+    Ok(())
+}
+
+/// Entry point for `se_runtime_host retire ...`.
+pub fn run_retire(args: &[String]) -> Result<()> {
+    let args = parse_args(args)?;
+    if args.force {
+        anyhow::bail!("--force only applies to revive");
+    }
+    let registry = registry_from_env(&args.root)?;
+
+    let reason = args
+        .reason
+        .as_deref()
+        .unwrap_or("retired from the command line");
+    Lifecycle::new(registry.as_ref()).retire(&args.id, ACTOR, reason)?;
+    println!("[LIFECYCLE] '{}' retired", args.id);
+    Ok(())
+}
+
+/// Entry point for `se_runtime_host history ...`.
+pub fn run_history(args: &[String]) -> Result<()> {
+    let args = parse_args(args)?;
+    let registry = registry_from_env(&args.root)?;
+
+    let log = Lifecycle::new(registry.as_ref()).audit_log(&args.id)?;
+    if log.is_empty() {
+        println!("[LIFECYCLE] No status changes recorded for '{}'", args.id);
+        return Ok(());
+    }
+
+    for event in &log {
+        println!("{}", format_event(event));
+    }
+    Ok(())
+}
+
+fn format_event(event: &RegistryEvent) -> String {
+    let RegistryChange::StatusChanged {
+        from,
+        to,
+        replaced_by,
+    } = &event.change
+    else {
+        return format!("{} {:?}", event.at, event.change);
+    };
+
+    let mut line = format!(
+        "{} {} {:?} -> {:?}",
+        event.at,
+        event.actor.as_deref().unwrap_or("-"),
+        from,
+        to
+    );
+    if let Some(replacement) = replaced_by {
+        line.push_str(&format!(" (replaced by {})", replacement));
+    }
+    if let Some(reason) = &event.reason {
+        line.push_str(&format!(": {}", reason));
+    }
+    line
+}
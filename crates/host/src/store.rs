@@ -1,81 +1,313 @@
 // crates/host/src/store.rs
 
-use std::fs;
-use std::path::Path;
+use std::collections::HashMap;
 
 use anyhow::Result;
-use serde_json::json;
 
-use se_runtime_core::capability_index::CapabilityIndex;
-use se_runtime_core::capability_registry::CapabilityRegistry;
+use se_runtime_core::capability_index::{CapabilityIndex, Retrieval, RetrievalOptions};
 use se_runtime_core::embedding::Embedder;
+use se_runtime_core::index_document::IndexDocumentOptions;
+use se_runtime_core::lifecycle::Lifecycle;
+use se_runtime_core::registry_backend::RegistryBackend;
+use se_runtime_core::registry_health::{load_validated, HealthReport, LoadMode};
+use se_runtime_core::run_stats::{self, CapabilityRunStats};
 use se_runtime_core::types::{CapabilityRecord, CapabilityStatus};
+use se_runtime_core::usage::UsageMeter;
+
+use crate::examples::{recent_tasks, CapabilityExample, ExampleLog};
+use crate::reranker::Reranker;
+
+/// Recorded examples merged into each capability's indexed `example_tasks`.
+const MAX_INDEXED_EXAMPLES: usize = 5;
+/// Recorded examples shown per capability in the agent's summary.
+const MAX_SUMMARY_EXAMPLES: usize = 2;
+
+/// Recorded examples per capability id, oldest first.
+type ExamplesById = HashMap<String, Vec<CapabilityExample>>;
 
 /// Pure state: the capabilities and their similarity index.
 /// This is what evolves over time as the agent creates new capabilities.
 pub struct CapabilityStore {
+    /// Where capability metadata is read from and written to.
+    registry: Box<dyn RegistryBackend>,
+    /// What to do with capabilities that fail validation.
+    load_mode: LoadMode,
     capabilities: Vec<CapabilityRecord>,
     index: CapabilityIndex,
+    /// What gets embedded per capability; kept for rebuilds.
+    document: IndexDocumentOptions,
+    /// Run history per capability id, as last read from or written to the registry.
+    run_stats: HashMap<String, CapabilityRunStats>,
+    example_log: ExampleLog,
+    examples: ExamplesById,
+    /// Weight of the run-history prior in `rank_for_task`.
+    usage_weight: f32,
 }
 
 impl CapabilityStore {
-    /// Load capabilities from disk and build the similarity index.
-    pub fn load(capabilities_root: &str, embedder: &impl Embedder) -> Result<Self> {
-        let registry = CapabilityRegistry::new(capabilities_root);
-        let mut capabilities = registry.load_capabilities()?;
-
-        if capabilities.is_empty() {
-            anyhow::bail!(
-                "No capabilities found under {} – add some meta.json files!",
-                capabilities_root
-            );
-        }
+    /// Load capabilities from the registry and build the similarity index.
+    ///
+    /// Capabilities that fail validation are quarantined or abort the load
+    /// depending on `load_mode`. Run stats and cached embeddings come from
+    /// the registry, example logs from `capabilities_root`.
+    pub fn load(
+        capabilities_root: &str,
+        registry: Box<dyn RegistryBackend>,
+        load_mode: LoadMode,
+        embedder: &impl Embedder,
+        document: IndexDocumentOptions,
+    ) -> Result<Self> {
+        let example_log = ExampleLog::new(capabilities_root);
+        let (mut capabilities, examples) = load_capabilities(
+            capabilities_root,
+            registry.as_ref(),
+            load_mode,
+            &example_log,
+        )?;
 
-        let index = CapabilityIndex::build(&mut capabilities, embedder)?;
+        let index = CapabilityIndex::build_cached(
+            &mut capabilities,
+            embedder,
+            &document,
+            registry.as_ref(),
+        )?;
+        let run_stats = registry.run_stats()?;
 
         Ok(Self {
+            registry,
+            load_mode,
             capabilities,
             index,
+            document,
+            run_stats,
+            example_log,
+            examples,
+            usage_weight: RetrievalOptions::default().usage_weight,
         })
     }
 
-    /// Rebuild the similarity index after capabilities change (for mutate_capability later).
-    pub fn rebuild_index(&mut self, embedder: &impl Embedder) -> Result<()> {
-        self.index = CapabilityIndex::build(&mut self.capabilities, embedder)?;
-        Ok(())
+    /// Set how strongly run history affects ranking (0 = similarity only).
+    pub fn set_usage_weight(&mut self, weight: f32) {
+        self.usage_weight = weight;
     }
 
-    /// Build a model-friendly summary of the k nearest capabilities for a given task.
-    /// Only includes active capabilities (not legacy or deprecated).
-    pub fn capabilities_summary_for_task(
+    /// Rank active capabilities for a task, best first, returning at most k.
+    ///
+    /// Legacy and deprecated capabilities are skipped rather than counted
+    /// against k. Scores blend similarity with each capability's run-history
+    /// prior according to the usage weight.
+    pub fn rank_for_task(
         &self,
         task: &str,
         embedder: &impl Embedder,
         k: usize,
-    ) -> Result<(String, Vec<(String, f32)>)> {
-        let nearest = self.index.nearest_for_task(task, embedder, k)?;
+    ) -> Result<Vec<(String, f32)>> {
+        let mut ranked: Vec<(String, f32)> = self
+            .similarity_for_task(task, embedder)?
+            .into_iter()
+            .map(|(id, similarity)| {
+                let score = self.blended_score(&id, similarity);
+                (id, score)
+            })
+            .collect();
+
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        ranked.truncate(k);
+        Ok(ranked)
+    }
 
-        // Filter to only active capabilities
-        let active_nearest: Vec<_> = nearest
+    /// Active capabilities by raw similarity to a task, best first.
+    fn similarity_for_task(
+        &self,
+        task: &str,
+        embedder: &impl Embedder,
+    ) -> Result<Vec<(String, f32)>> {
+        let nearest = self
+            .index
+            .nearest_for_task(task, embedder, self.index.len())?;
+
+        Ok(nearest
             .into_iter()
             .filter(|(id, _)| {
-                self.capabilities
-                    .iter()
-                    .find(|c| &c.id == id)
+                self.get_capability(id)
                     .map(|c| c.is_active())
                     .unwrap_or(false)
             })
+            .collect())
+    }
+
+    /// `(1 - w) * similarity + w * prior`, used for ordering only.
+    fn blended_score(&self, capability_id: &str, similarity: f32) -> f32 {
+        let w = self.usage_weight;
+        if w > 0.0 {
+            (1.0 - w) * similarity + w * run_stats::prior(&self.run_stats, capability_id)
+        } else {
+            similarity
+        }
+    }
+
+    /// Run history for a capability, if it has ever run.
+    pub fn run_stats(&self, capability_id: &str) -> Option<&CapabilityRunStats> {
+        self.run_stats.get(capability_id)
+    }
+
+    /// Record the outcome of running a capability and persist it.
+    pub fn record_run(
+        &mut self,
+        capability_id: &str,
+        success: bool,
+        latency_ms: u64,
+    ) -> Result<()> {
+        let stats = self
+            .registry
+            .record_run(capability_id, success, latency_ms)?;
+        self.run_stats.insert(capability_id.to_string(), stats);
+        Ok(())
+    }
+
+    /// Recorded examples for a capability, oldest first.
+    pub fn examples(&self, capability_id: &str) -> &[CapabilityExample] {
+        self.examples
+            .get(capability_id)
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Remember that running `capability_id` with `input_json` helped solve `task`.
+    ///
+    /// The example is shown in summaries right away and indexed on the next
+    /// load or reload.
+    pub fn record_example(
+        &mut self,
+        task: &str,
+        capability_id: &str,
+        input_json: &str,
+    ) -> Result<()> {
+        let example = CapabilityExample {
+            task: task.to_string(),
+            input_json: input_json.to_string(),
+            capability_id: capability_id.to_string(),
+            recorded_at: Some(chrono::Utc::now().to_rfc3339()),
+        };
+
+        if self.example_log.append(&example)? {
+            println!("[STORE] Recorded example for '{}'", capability_id);
+            self.examples
+                .entry(capability_id.to_string())
+                .or_default()
+                .push(example);
+        }
+        Ok(())
+    }
+
+    /// Select the capabilities relevant to a task using similarity
+    /// thresholds, ordered by the blended score, with MMR reranking when
+    /// enabled in `opts`.
+    ///
+    /// With a reranker, its top `candidate_count()` candidates by raw
+    /// similarity are rescored by the reranker instead, with its model calls
+    /// charged to `usage`; if it fails, the embedding-only selection is used.
+    pub fn retrieve_for_task(
+        &self,
+        task: &str,
+        embedder: &impl Embedder,
+        opts: &RetrievalOptions,
+        reranker: Option<&dyn Reranker>,
+        usage: &mut UsageMeter,
+    ) -> Result<Retrieval> {
+        let by_similarity = self.similarity_for_task(task, embedder)?;
+
+        if let Some(reranker) = reranker {
+            match self.rerank(task, &by_similarity, opts, reranker, usage) {
+                Ok(retrieval) => return Ok(retrieval),
+                Err(e) => println!("[RERANK] Failed, using similarity ranking: {:#}", e),
+            }
+        }
+
+        // Run history reorders matches but doesn't decide what qualifies.
+        Ok(self.index.select_by(by_similarity, opts, |id, similarity| {
+            self.blended_score(id, similarity)
+        }))
+    }
+
+    /// Second retrieval stage: let the reranker score the shortlist.
+    fn rerank(
+        &self,
+        task: &str,
+        ranked: &[(String, f32)],
+        opts: &RetrievalOptions,
+        reranker: &dyn Reranker,
+        usage: &mut UsageMeter,
+    ) -> Result<Retrieval> {
+        let shortlist: Vec<&CapabilityRecord> = ranked
+            .iter()
+            .take(reranker.candidate_count())
+            .filter_map(|(id, _)| self.get_capability(id))
             .collect();
 
+        let scores = reranker.rerank(task, &shortlist, usage)?;
+
+        let matches: Vec<(String, f32)> = scores
+            .into_iter()
+            .filter(|s| s.score >= reranker.min_score())
+            .take(opts.max_k)
+            .map(|s| (s.id, s.score))
+            .collect();
+
+        let closest = if matches.is_empty() {
+            ranked.iter().take(opts.max_k).cloned().collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Retrieval { matches, closest })
+    }
+
+    /// Build a model-friendly summary of the capabilities relevant to a task.
+    /// Only includes active capabilities (not legacy or deprecated).
+    ///
+    /// When nothing clears the relevance threshold the summary says so and
+    /// lists the closest capabilities as candidate parents for mutation.
+    pub fn capabilities_summary_for_task(
+        &self,
+        task: &str,
+        embedder: &impl Embedder,
+        opts: &RetrievalOptions,
+        reranker: Option<&dyn Reranker>,
+        usage: &mut UsageMeter,
+    ) -> Result<(String, Retrieval)> {
+        let retrieval = self.retrieve_for_task(task, embedder, opts, reranker, usage)?;
+
         let mut lines = Vec::new();
-        lines.push("You have access to the following capabilities:".to_string());
-        for (id, _score) in &active_nearest {
-            if let Some(cap) = self.capabilities.iter().find(|c| &c.id == id) {
+        if retrieval.is_no_match() {
+            lines.push(
+                "No existing capability is a good match for this task.\n\
+                 A new capability is likely needed: use mutate_capability, picking the closest capability below as the parent.\n\
+                 Closest capabilities:"
+                    .to_string(),
+            );
+        } else {
+            lines.push("You have access to the following capabilities:".to_string());
+        }
+
+        let listed = if retrieval.is_no_match() {
+            &retrieval.closest
+        } else {
+            &retrieval.matches
+        };
+        for (id, _score) in listed {
+            if let Some(cap) = self.get_capability(id) {
                 lines.push(format!("- id: {}\n  summary: {}", cap.id, cap.summary));
+                for example in self.examples(id).iter().rev().take(MAX_SUMMARY_EXAMPLES) {
+                    lines.push(format!(
+                        "  example: task {:?} -> input_json {}",
+                        example.task, example.input_json
+                    ));
+                }
             }
         }
 
-        Ok((lines.join("\n"), active_nearest))
+        Ok((lines.join("\n"), retrieval))
     }
 
     /// Lookup a capability by id.
@@ -88,73 +320,113 @@ impl CapabilityStore {
         self.capabilities.len()
     }
 
-    /// Check if store is empty.
-    pub fn is_empty(&self) -> bool {
-        self.capabilities.is_empty()
+    /// The registry backend holding capability metadata.
+    pub fn registry(&self) -> &dyn RegistryBackend {
+        self.registry.as_ref()
     }
 
-    /// Reload all capabilities from disk (used after mutation creates new ones).
+    /// Reload all capabilities from the registry (used after mutation creates new ones).
     pub fn reload(&mut self, capabilities_root: &str, embedder: &impl Embedder) -> Result<()> {
-        let registry = CapabilityRegistry::new(capabilities_root);
-        let mut capabilities = registry.load_capabilities()?;
-
-        if capabilities.is_empty() {
-            anyhow::bail!(
-                "No capabilities found under {} – add some meta.json files!",
-                capabilities_root
-            );
-        }
+        let example_log = ExampleLog::new(capabilities_root);
+        let (mut capabilities, examples) = load_capabilities(
+            capabilities_root,
+            self.registry.as_ref(),
+            self.load_mode,
+            &example_log,
+        )?;
 
-        let index = CapabilityIndex::build(&mut capabilities, embedder)?;
+        let index = CapabilityIndex::build_cached(
+            &mut capabilities,
+            embedder,
+            &self.document,
+            self.registry.as_ref(),
+        )?;
 
         self.capabilities = capabilities;
         self.index = index;
+        self.example_log = example_log;
+        self.examples = examples;
 
         println!("[STORE] Reloaded {} capabilities", self.capabilities.len());
         Ok(())
     }
 
     /// Mark a capability as deprecated (broken/non-functional).
-    /// Updates both in-memory state and meta.json on disk.
-    pub fn mark_deprecated(
-        &mut self,
-        capabilities_root: &str,
-        capability_id: &str,
-        reason: &str,
-    ) -> Result<()> {
-        // Update in-memory state
+    /// Updates both in-memory state and the registry.
+    pub fn mark_deprecated(&mut self, capability_id: &str, reason: &str) -> Result<()> {
+        if self.get_capability(capability_id).is_none() {
+            anyhow::bail!("Capability '{}' not found", capability_id);
+        }
+
+        Lifecycle::new(self.registry.as_ref()).deprecate(capability_id, "agent", reason)?;
+
         if let Some(cap) = self.capabilities.iter_mut().find(|c| c.id == capability_id) {
             cap.status = CapabilityStatus::Deprecated;
-        } else {
-            anyhow::bail!("Capability '{}' not found", capability_id);
         }
 
-        // Update meta.json on disk
-        let meta_path = Path::new(capabilities_root)
-            .join("crates")
-            .join(capability_id)
-            .join("meta.json");
-
-        if !meta_path.exists() {
-            anyhow::bail!(
-                "meta.json not found for capability '{}' at {}",
-                capability_id,
-                meta_path.display()
+        println!(
+            "[STORE] Marked '{}' as deprecated: {}",
+            capability_id, reason
+        );
+        Ok(())
+    }
+}
+
+/// Load and validate capabilities from the registry, merging recently
+/// recorded example tasks into each record so they're indexed.
+fn load_capabilities(
+    capabilities_root: &str,
+    registry: &dyn RegistryBackend,
+    load_mode: LoadMode,
+    example_log: &ExampleLog,
+) -> Result<(Vec<CapabilityRecord>, ExamplesById)> {
+    let (mut capabilities, report) = load_validated(registry, capabilities_root, load_mode)?;
+    print_health(&report);
+
+    if capabilities.is_empty() {
+        anyhow::bail!(
+            "No capabilities found under {} – add some meta.json files!",
+            capabilities_root
+        );
+    }
+
+    let mut examples = HashMap::new();
+    for cap in capabilities.iter_mut() {
+        // Examples only improve retrieval; a bad log must not block startup.
+        let recorded = example_log.load(&cap.id).unwrap_or_else(|e| {
+            println!(
+                "[STORE] Warning: ignoring examples for '{}': {:#}",
+                cap.id, e
             );
+            Vec::new()
+        });
+        for task in recent_tasks(&recorded, MAX_INDEXED_EXAMPLES) {
+            if !cap.example_tasks.contains(&task) {
+                cap.example_tasks.push(task);
+            }
         }
+        if !recorded.is_empty() {
+            examples.insert(cap.id.clone(), recorded);
+        }
+    }
 
-        let content = fs::read_to_string(&meta_path)?;
-        let mut meta: serde_json::Value = serde_json::from_str(&content)?;
-
-        meta["status"] = json!("deprecated");
-        meta["deprecated_reason"] = json!(reason);
+    Ok((capabilities, examples))
+}
 
-        fs::write(&meta_path, serde_json::to_string_pretty(&meta)?)?;
+/// Log quarantined capabilities in full and warnings as a count.
+fn print_health(report: &HealthReport) {
+    for issue in report.errors() {
+        println!(
+            "[STORE] Quarantined '{}': {}",
+            issue.capability_id, issue.kind
+        );
+    }
 
+    let warnings = report.warnings().count();
+    if warnings > 0 {
         println!(
-            "[STORE] Marked '{}' as deprecated: {}",
-            capability_id, reason
+            "[STORE] {} registry warnings (run `se_runtime_host check` for details)",
+            warnings
         );
-        Ok(())
     }
 }
@@ -0,0 +1,50 @@
+// crates/host/src/stream_printer.rs
+
+//! Prints streamed assistant text and tool-call arguments as they arrive,
+//! so long completions (e.g. a full `main.rs`) don't look like a hang.
+
+use std::io::Write;
+
+use se_runtime_core::streaming::StreamDelta;
+
+#[derive(Debug, Default)]
+pub struct StreamPrinter {
+    /// Something was printed since the last newline.
+    mid_line: bool,
+}
+
+impl StreamPrinter {
+    pub fn on_delta(&mut self, delta: StreamDelta<'_>) {
+        let mut out = std::io::stdout().lock();
+        match delta {
+            StreamDelta::Content(text) => {
+                let _ = write!(out, "{}", text);
+                self.mid_line = !text.ends_with('\n');
+            }
+            StreamDelta::ToolCall {
+                name, arguments, ..
+            } => {
+                if let Some(name) = name {
+                    if self.mid_line {
+                        let _ = writeln!(out);
+                    }
+                    let _ = write!(out, "[STREAM] {}: ", name);
+                    self.mid_line = true;
+                }
+                let _ = write!(out, "{}", arguments);
+                if !arguments.is_empty() {
+                    self.mid_line = !arguments.ends_with('\n');
+                }
+            }
+        }
+        let _ = out.flush();
+    }
+
+    /// End the streamed output on its own line.
+    pub fn finish(&mut self) {
+        if self.mid_line {
+            println!();
+            self.mid_line = false;
+        }
+    }
+}